@@ -0,0 +1,79 @@
+import { test, expect } from '@playwright/test';
+import { adminConfigured, signInAsAdmin, submitInquiry } from './inquiry';
+import { mailpitRunning, waitForMail } from './mailpit';
+
+test.describe('Admin inbox', () => {
+  test('redirects to login when not authenticated', async ({ page }) => {
+    await page.goto('/admin');
+
+    await expect(page).toHaveURL(/\/admin\/login/);
+    await expect(page.getByRole('heading', { name: 'Logga in' })).toBeVisible();
+  });
+
+  test('rejects invalid credentials', async ({ page }) => {
+    await page.goto('/admin/login');
+
+    await page.locator('#username').fill('not-an-admin');
+    await page.locator('#password').fill('wrong-password');
+    await page.getByRole('button', { name: 'Logga in' }).click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(page).toHaveURL(/error=InvalidCredentials/);
+    await expect(page.locator('p.error-message')).toHaveText('Fel användarnamn eller lösenord.');
+  });
+
+  test.describe('Replies', () => {
+    test.beforeEach(async () => {
+      test.skip(!adminConfigured(), 'sign in with ADMIN_USERNAME and ADMIN_PASSWORD from the dev shell');
+      test.skip(!(await mailpitRunning()), 'start mailpit from the dev shell to test outgoing mail');
+    });
+
+    test('mails a reply and shows when it has been sent', async ({ page, browserName }) => {
+      test.setTimeout(60 * 1000);
+      const email = `svar.${browserName}.${Date.now()}@example.com`;
+      const id = await submitInquiry(page, `Fråga som ska besvaras ${Date.now()}`, email);
+      const reply = `Hej! Vi tittar på det i morgon. ${Date.now()}`;
+
+      await signInAsAdmin(page);
+      await page.goto(`/admin/inquiries/${id}`);
+      await page.locator('#reply').fill(reply);
+      await page.getByRole('button', { name: 'Skicka svar' }).click();
+      await page.waitForLoadState('domcontentloaded');
+
+      const outgoing = page.locator('.admin-thread li.outgoing').filter({ hasText: reply });
+      await expect(outgoing).toHaveCount(1);
+      await expect(outgoing.locator('.admin-thread-meta')).toContainText(`(${process.env.ADMIN_USERNAME})`);
+
+      const mail = await waitForMail(email, reply);
+      expect(mail.subject).toBe(`Sv: Ditt ärende hos Tivander IT (TIT-${String(id).padStart(6, '0')})`);
+      // REPLY_ADDRESS in the dev shell, with a token for the inquiry
+      expect(mail.replyTo).toEqual([expect.stringMatching(new RegExp(`^svar\\+${id}\\.[0-9a-f]+@tivanderit\\.test$`))]);
+
+      // The job marks the reply as sent once the mail has gone out
+      await expect(async () => {
+        await page.reload();
+        await expect(outgoing.locator('.admin-delivery')).toHaveText('Skickat');
+      }).toPass({ timeout: 10000 });
+    });
+
+    test('refuses to reply to an inquiry deleted in the meantime', async ({ page, context, browserName }) => {
+      test.setTimeout(60 * 1000);
+      const id = await submitInquiry(page, `Fråga som raderas ${Date.now()}`, `raderas.${browserName}.${Date.now()}@example.com`);
+
+      await signInAsAdmin(page);
+      await page.goto(`/admin/inquiries/${id}`);
+      await page.locator('#reply').fill('Ett svar som aldrig skickas.');
+
+      const other = await context.newPage();
+      await other.goto(`/admin/inquiries/${id}`);
+      await other.getByRole('button', { name: 'Radera ärendet' }).click();
+      await other.waitForLoadState('domcontentloaded');
+      await expect(other).toHaveURL(/\/admin$/);
+
+      await page.getByRole('button', { name: 'Skicka svar' }).click();
+      await page.waitForLoadState('domcontentloaded');
+      await expect(page).toHaveURL(new RegExp(`/admin/inquiries/${id}\\?error=NotFound`));
+      await expect(page.locator('p.error-message').first()).toHaveText('Ärendet kunde inte hittas.');
+    });
+  });
+});
@@ -0,0 +1,151 @@
+import { test, expect, type Page } from '@playwright/test';
+import { mailpitRunning, waitForMail } from './mailpit';
+
+const BOOKING_PAGE_PATH = '/#booking';
+
+// Bookings don't send a proof of work, so the server requires the no-JS fill time
+// (CONTACT_FORM_NOJS_MIN_FILL_SECS).
+const MIN_FILL_MS = 10000;
+
+const locators = (page: Page) => ({
+  form: page.getByTestId('booking-form'),
+  days: page.locator('details.booking-day'),
+  firstSlot: page.locator('.booking-times input[type="radio"]:not([disabled])').first(),
+  descriptionInput: page.locator('#booking-description'),
+  emailInput: page.locator('#booking-email'),
+  termsCheckbox: page.locator('#booking-terms'),
+  submitButton: page.getByTestId('booking-form-submit'),
+  successMessage: page.locator('#booking p.success-message'),
+  slotError: page.locator('#booking-slot-error'),
+  descriptionError: page.locator('#booking-description-error'),
+});
+
+let locator: ReturnType<typeof locators>;
+let renderedAt = 0;
+
+async function waitForMinFillTime() {
+  const remaining = MIN_FILL_MS - (Date.now() - renderedAt);
+  if (remaining > 0) {
+    await new Promise((resolve) => setTimeout(resolve, remaining + 100));
+  }
+}
+
+test.beforeEach(async ({ page }) => {
+  locator = locators(page);
+  await page.goto(BOOKING_PAGE_PATH);
+  await expect(locator.submitButton).toBeVisible();
+  renderedAt = Date.now();
+});
+
+test.describe('Booking', () => {
+  test('shows free slots, with the first day opened', async () => {
+    await expect(locator.form).toBeAttached();
+    await expect(locator.days.first()).toHaveAttribute('open', '');
+    await expect(locator.firstSlot).toBeAttached();
+    await expect(locator.submitButton).toBeDisabled();
+  });
+
+  test('enables booking once a slot is picked and the form is filled in', async ({ page }) => {
+    await expect(locator.form).toBeAttached();
+    await page.locator('.booking-times label').first().click();
+    await locator.descriptionInput.fill('Datorn startar inte.');
+    await locator.emailInput.fill('valid.user@example.com');
+    await locator.termsCheckbox.setChecked(true);
+
+    await expect(locator.submitButton).toBeEnabled();
+  });
+
+  test('rejects a link with an invalid signature', async ({ page }) => {
+    await page.goto('/booking/1?action=confirm&sig=00');
+
+    await expect(page.locator('#booking-details p.error-message')).toHaveText(
+      'Länken är ogiltig. Kontrollera att du har kopierat hela länken från mejlet.'
+    );
+    await expect(page.getByRole('button', { name: 'Bekräfta bokningen' })).toHaveCount(0);
+  });
+});
+
+test.describe('Booking (JavaScript Disabled)', () => {
+  test.use({ javaScriptEnabled: false });
+
+  test('shows every missing field at once', async ({ page }) => {
+    await locator.emailInput.fill('valid.user@example.com');
+    await locator.termsCheckbox.setChecked(true);
+    await waitForMinFillTime();
+    await locator.submitButton.click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(page).toHaveURL(/booking_error=MissingSlot,MissingDescription/);
+    await expect(locator.slotError).toHaveText('Välj en tid.');
+    await expect(locator.descriptionError).toHaveText('Beskriv kort vad du behöver hjälp med.');
+  });
+
+  // Every address can only hold a few unconfirmed bookings (BOOKING_MAX_PENDING)
+  const uniqueEmail = (browserName: string) => `bokning.${browserName}.${Date.now()}@example.com`;
+
+  async function book(page: Page, email: string) {
+    await locator.firstSlot.check({ force: true });
+    await locator.descriptionInput.fill('Datorn har blivit väldigt långsam.');
+    await locator.emailInput.fill(email);
+    await locator.termsCheckbox.setChecked(true);
+    await waitForMinFillTime();
+    await locator.submitButton.click();
+    await page.waitForLoadState('domcontentloaded');
+  }
+
+  test('reserves a slot and asks for confirmation', async ({ page, browserName }) => {
+    await book(page, uniqueEmail(browserName));
+
+    await expect(page).toHaveURL(/booking=pending/);
+    await expect(locator.successMessage).toBeVisible();
+  });
+
+  test('limits the unconfirmed bookings of an address', async ({ page, browserName }) => {
+    test.setTimeout(60 * 1000);
+    const email = uniqueEmail(browserName);
+
+    for (let booked = 0; booked < 2; booked++) {
+      await book(page, email);
+      await expect(page).toHaveURL(/booking=pending/);
+      await page.goto(BOOKING_PAGE_PATH);
+      renderedAt = Date.now();
+    }
+    await book(page, email);
+
+    await expect(page).toHaveURL(/booking_error=TooManyPending/);
+    await expect(page.locator('#booking p.general-error')).toContainText('väntar på bekräftelse');
+  });
+
+  test('mails the confirmation link and announces the confirmed booking', async ({ page, browserName }) => {
+    test.skip(!(await mailpitRunning()), 'start mailpit from the dev shell to test outgoing mail');
+    test.setTimeout(60 * 1000);
+    const email = uniqueEmail(browserName);
+
+    await book(page, email);
+    await expect(page).toHaveURL(/booking=pending/);
+
+    // Queued in the same transaction as the booking
+    const request = await waitForMail(email, 'Bekräfta bokningen');
+    expect(request.subject).toBe('Bekräfta din bokning hos Tivander IT');
+    const confirmUrl = /http\S+\/booking\/\d+\?action=confirm&sig=\S+/.exec(request.text)![0];
+    expect(request.text).toMatch(/action=cancel&sig=/);
+
+    await page.goto(confirmUrl);
+    await page.getByRole('button', { name: 'Bekräfta bokningen' }).click();
+    await page.waitForLoadState('domcontentloaded');
+    await expect(page.locator('#booking-details dd').nth(1)).toHaveText('Bekräftad');
+
+    // NOTIFY_EMAIL in the dev shell
+    const notification = await waitForMail('kontakt@tivanderit.test', email);
+    expect(notification.subject).toMatch(/^Ny bokning .+ från /);
+    expect(notification.replyTo).toEqual([email]);
+  });
+});
+
+test.describe('Admin bookings', () => {
+  test('redirects to login when not authenticated', async ({ page }) => {
+    await page.goto('/admin/bookings');
+
+    await expect(page).toHaveURL(/\/admin\/login/);
+  });
+});
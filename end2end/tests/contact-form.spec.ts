@@ -1,4 +1,7 @@
 import { test, expect, type Page } from '@playwright/test';
+import { createHmac } from 'node:crypto';
+import { createServer, type IncomingHttpHeaders, type Server } from 'node:http';
+import { mailpitRunning, waitForMail } from './mailpit';
 
 const CONTACT_PAGE_PATH = '/#contact';
 
@@ -6,30 +9,57 @@ const locators = (page: Page) => ({
   messageInput: page.locator('#message'),
   emailInput: page.locator('#email'),
   termsCheckbox: page.locator('#terms'),
+  serviceSelect: page.locator('#service_type'),
+  deadlineInput: page.locator('#deadline'),
   submitButton: page.getByTestId('contact-form-submit'),
   successMessage: page.locator('.success-message'),
   form: page.getByTestId('contact-form'),
   messageError: page.locator('#message-error'),
   emailError: page.locator('#email-error'),
   termsError: page.locator('#terms-error'),
+  serviceError: page.locator('#service-type-error'),
+  deadlineError: page.locator('#deadline-error'),
   generalError: page.locator('p.error-message.general-error'), 
   messageHint: page.locator('div.form-field:has(#message) p.hint-message'), 
   emailHint: page.locator('div.form-field:has(#email) p.hint-message'), 
+  charCounter: page.locator('p.char-counter'),
+  attachmentsInput: page.locator('#attachments'),
+  attachmentsError: page.locator('#attachments-error'),
 });
 
-async function fillForm(locator: ReturnType<typeof locators>, options: {message?:string; email?:string; terms?:boolean} = {}) {
+// Just the PNG signature, which is what the server looks at.
+const PNG = Buffer.from([0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d]);
+
+// The server rejects forms submitted sooner than this after being rendered. Without JS there is
+// no proof of work, so the wait is longer (CONTACT_FORM_MIN_FILL_SECS / CONTACT_FORM_NOJS_MIN_FILL_SECS).
+const MIN_FILL_MS = 3000;
+const NOJS_MIN_FILL_MS = 10000;
+let minFillMs = MIN_FILL_MS;
+let renderedAt = 0;
+
+async function waitForMinFillTime() {
+  const remaining = minFillMs - (Date.now() - renderedAt);
+  if (remaining > 0) {
+    await new Promise((resolve) => setTimeout(resolve, remaining + 100));
+  }
+}
+
+async function fillForm(locator: ReturnType<typeof locators>, options: {message?:string; email?:string; terms?:boolean; service?:string} = {}) {
     const {
       message = 'Detta är ett giltigt testmeddelande.',
       email = 'valid.user@example.com',
       terms = true,
+      service = 'pc_help',
     } = options;
 
+    await locator.serviceSelect.selectOption(service);
     await locator.termsCheckbox.setChecked(terms);
     await locator.messageInput.clear();
     await locator.messageInput.pressSequentially(message);
     await locator.emailInput.clear();
     await locator.emailInput.pressSequentially(email);
     await locator.emailInput.blur();
+    await waitForMinFillTime();
 }
 
 let locator: ReturnType<typeof locators>;
@@ -40,6 +70,8 @@ test.beforeEach(async ({ page }) => {
   // Navigate to form and wait for it to render.
   await page.goto(CONTACT_PAGE_PATH);
   await expect(locator.messageInput).toBeVisible();
+  renderedAt = Date.now();
+  minFillMs = MIN_FILL_MS;
 });
 
 test.describe('Contact Form Functionality', () => {
@@ -83,6 +115,26 @@ test.describe('Contact Form Functionality', () => {
     await expect(locator.emailInput).toHaveValue('');
     await expect(locator.termsCheckbox).not.toBeChecked();  });
 
+ test('sends a solved proof of work with the submission', async ({ page }) => {
+    await fillForm(locator);
+    await expect(locator.submitButton).toBeEnabled();
+
+    const request = page.waitForRequest((request) => request.url().includes('/api/store_contact_form'));
+    await locator.submitButton.click();
+
+    expect((await request).postData()).toMatch(/pow_solution=\d+/);
+    await expect(locator.successMessage).toBeVisible();
+  });
+
+ test('rejects an attachment that is not an image or PDF', async () => {
+    await fillForm(locator);
+    await locator.attachmentsInput.setInputFiles({ name: 'anteckningar.txt', mimeType: 'text/plain', buffer: Buffer.from('hej') });
+    await locator.submitButton.click();
+
+    await expect(locator.attachmentsError).toHaveText('Endast bilder (PNG, JPEG, GIF, WebP) och PDF-filer kan bifogas.');
+    await expect(locator.successMessage).toBeHidden();
+  });
+
  test.describe('Client-Side Validation', () => {
     test('disables submit button if message is empty', async () => {
       await fillForm(locator, { message: '' });
@@ -103,13 +155,30 @@ test.describe('Contact Form Functionality', () => {
       await fillForm(locator, { email: 'invalid' });
 
       await expect(locator.emailHint).toBeVisible();
-      await expect(locator.emailHint).toHaveText('Ange en giltig email.');
+      await expect(locator.emailHint).toHaveText('Ange en giltig email-adress.');
       await expect(locator.emailError).toBeHidden();
 
       await fillForm(locator);
       await expect(locator.emailHint).toBeHidden();
     });
 
+    test('shows hint for malformed email addresses', async () => {
+      for (const email of ['a@b@c.se', 'namn @exempel.se', 'namn@exempel', 'namn@-exempel.se']) {
+        await fillForm(locator, { email });
+        await expect(locator.emailHint).toHaveText('Ange en giltig email-adress.');
+        await expect(locator.submitButton).toBeDisabled();
+      }
+    });
+
+    test('accepts internationalised email addresses', async () => {
+      await fillForm(locator, { email: 'åsa.öberg@exempel.se' });
+      await expect(locator.emailHint).toBeHidden();
+
+      await fillForm(locator, { email: 'info@räksmörgås.se' });
+      await expect(locator.emailHint).toBeHidden();
+      await expect(locator.submitButton).toBeEnabled();
+    });
+
     test('shows hint for empty message', async () => {
       await fillForm(locator, { message: '' });
 
@@ -120,31 +189,45 @@ test.describe('Contact Form Functionality', () => {
       await fillForm(locator);
       await expect(locator.messageHint).toBeHidden();
     });
-  });
- 
-  test.describe('Server-Side Validation', () => {
-    test('shows general error for message too long', async () => {
+
+    test('counts Swedish letters as one character each', async () => {
+      await locator.messageInput.fill('åäö'.repeat(1000));
+
+      await expect(locator.charCounter).toHaveText('2000 tecken kvar');
+    });
+
+    test('disables submit button and shows hint for message too long', async () => {
       const longMessage = 'a'.repeat(5001);
       await fillForm(locator, { message: longMessage });
-      await expect(locator.submitButton).toBeEnabled();
 
-      await locator.submitButton.click();
+      await expect(locator.submitButton).toBeDisabled();
+      await expect(locator.messageHint).toHaveText('Meddelandet är för långt (max 5000 tecken).');
+    });
+
+    test('limits a message of few letters but many bytes', async () => {
+      await fillForm(locator, { message: 'kort' });
+      // One letter with thousands of combining accents is still one letter
+      await locator.messageInput.fill('a' + '\u0301'.repeat(40000));
+      await locator.messageInput.blur();
+
+      await expect(locator.submitButton).toBeDisabled();
+      await expect(locator.messageHint).toHaveText('Meddelandet tar för mycket plats (max 40 000 byte). Ta bort tecken med många accenter eller symboler.');
+    });
+
+    test('counts the length of an email address in characters', async () => {
+      // 159 letters, but 311 bytes
+      await fillForm(locator, { email: 'å'.repeat(32) + '@' + Array(4).fill('ö'.repeat(30)).join('.') + '.se' });
 
-      await expect(locator.messageError).toBeVisible(); // Changed from generalError
-      await expect(locator.messageError).toHaveText('Meddelandet är för långt (max 5000 tecken).'); // Changed from generalError
-      await expect(locator.successMessage).toBeHidden();
+      await expect(locator.emailHint).toBeHidden();
+      await expect(locator.submitButton).toBeEnabled();
     });
 
-    test('shows general error for email too long', async () => {
+    test('disables submit button and shows hint for email too long', async () => {
       const longEmail = 'a'.repeat(245) + '@example.com';
       await fillForm(locator, { email: longEmail });
-      await expect(locator.submitButton).toBeEnabled();
 
-      await locator.submitButton.click();
-      
-      await expect(locator.emailError).toBeVisible(); // Changed from generalError
-      await expect(locator.emailError).toHaveText('E-postadressen är för lång (max 254 tecken).'); // Changed from generalError
-      await expect(locator.successMessage).toBeHidden();
+      await expect(locator.submitButton).toBeDisabled();
+      await expect(locator.emailHint).toHaveText('E-postadressen är för lång (max 254 tecken).');
     });
   });
 });
@@ -153,6 +236,10 @@ test.describe('Contact Form Functionality', () => {
 test.describe('Contact Form Functionality (JavaScript Disabled)', () => {
   test.use({ javaScriptEnabled: false });
 
+  test.beforeEach(() => {
+    minFillMs = NOJS_MIN_FILL_MS;
+  });
+
   test('submits successfully with valid data (JS disabled)', async ({ page }) => {
     await fillForm(locator);
     await locator.submitButton.click();
@@ -188,4 +275,274 @@ test.describe('Contact Form Functionality (JavaScript Disabled)', () => {
     // Verify that success is *not* displayed
     await expect(locator.successMessage).toBeHidden();
   });
+
+  test('accepts a message of 5000 Swedish letters (JS disabled)', async ({ page }) => {
+    await fillForm(locator, { message: 'kort' });
+    await locator.messageInput.fill('å'.repeat(5000));
+    await locator.submitButton.click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(locator.successMessage).toBeVisible();
+  });
+
+  test('shows every server-side validation error at once (JS disabled)', async ({ page }) => {
+    await fillForm(locator, { message: '', terms: false });
+    await locator.submitButton.click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(page).toHaveURL(/error=MissingMessage,TermsNotAccepted/);
+    await expect(locator.messageError).toHaveText('Meddelandet får inte vara tomt.');
+    await expect(locator.termsError).toHaveText('Du måste acceptera villkoren för att skicka meddelandet.');
+    await expect(locator.emailError).toBeHidden();
+    await expect(locator.generalError).toBeHidden();
+  });
+
+  test('shows server-side validation error for message too long (JS disabled)', async ({ page }) => {
+    await fillForm(locator, { message: 'kort' });
+    await locator.messageInput.fill('a'.repeat(5001));
+    await locator.submitButton.click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(locator.messageError).toHaveText('Meddelandet är för långt (max 5000 tecken).');
+    await expect(locator.successMessage).toBeHidden();
+  });
+
+  test('submits successfully with a screenshot attached (JS disabled)', async ({ page }) => {
+    await fillForm(locator);
+    await locator.attachmentsInput.setInputFiles({ name: 'skarmdump.png', mimeType: 'image/png', buffer: PNG });
+    await locator.submitButton.click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(locator.successMessage).toBeVisible();
+  });
+
+  test('rejects an address on a disposable mail domain (JS disabled)', async ({ page }) => {
+    await fillForm(locator, { email: 'someone@mailinator.com' });
+    await locator.submitButton.click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(page).toHaveURL(/error=DomainNotAllowed/);
+    await expect(locator.generalError).toBeVisible();
+    await expect(locator.successMessage).toBeHidden();
+  });
+
+  test('rejects a submission with the honeypot filled in (JS disabled)', async ({ page }) => {
+    await fillForm(locator);
+    await page.locator('#website').fill('https://spam.example.com', { force: true });
+    await locator.submitButton.click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(page).toHaveURL(/error=SpamSuspected/);
+    await expect(locator.generalError).toBeVisible();
+    await expect(locator.successMessage).toBeHidden();
+  });
+
+  test('answers a resent submission with success without storing it again (JS disabled)', async ({ page }) => {
+    const message = `Dubbelskickat meddelande ${Date.now()}`;
+    await fillForm(locator, { message });
+    await locator.submitButton.click();
+    await page.waitForLoadState('domcontentloaded');
+    await expect(locator.successMessage).toBeVisible();
+
+    // A fresh form with the same message, as when a browser resends the POST
+    await page.goto(CONTACT_PAGE_PATH);
+    renderedAt = Date.now();
+    minFillMs = NOJS_MIN_FILL_MS;
+    await fillForm(locator, { message });
+    await locator.submitButton.click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(page).toHaveURL(/status=success/);
+    await expect(locator.successMessage).toBeVisible();
+  });
+
+  test('rejects a repeated idempotency key with another message (JS disabled)', async ({ page, request }) => {
+    const idempotencyKey = await page.locator('input[name="idempotency_key"]').getAttribute('value');
+    await fillForm(locator, { message: `Första meddelandet ${Date.now()}` });
+    await locator.submitButton.click();
+    await page.waitForLoadState('domcontentloaded');
+    await expect(locator.successMessage).toBeVisible();
+
+    const response = await request.post('/api/store_contact_form', {
+      multipart: { message: 'Ett helt annat meddelande', email: 'valid.user@example.com', service_type: 'pc_help', idempotency_key: idempotencyKey! },
+      maxRedirects: 0,
+    });
+    expect(response.headers()['location']).toMatch(/error=FormExpired/);
+  });
+
+  test('accepts a rendered form only once (JS disabled)', async ({ page, request }) => {
+    const formToken = await page.locator('input[name="form_token"]').getAttribute('value');
+    const terms = await locator.termsCheckbox.getAttribute('value');
+    await waitForMinFillTime();
+
+    // As a bot would post it, with a new message and no idempotency key every time
+    const post = (message: string) => request.post('/api/store_contact_form', {
+      multipart: { message, email: 'valid.user@example.com', service_type: 'pc_help', terms: terms!, form_token: formToken! },
+      maxRedirects: 0,
+    });
+    expect((await post(`Första gången ${Date.now()}`)).headers()['location']).toMatch(/status=success/);
+    expect((await post(`Andra gången ${Date.now()}`)).headers()['location']).toMatch(/error=FormExpired/);
+  });
+
+  test('requires a service type and a deadline that has not passed (JS disabled)', async ({ page }) => {
+    await fillForm(locator, { service: '' });
+    await locator.deadlineInput.fill('2000-01-01');
+    await locator.submitButton.click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(page).toHaveURL(/error=MissingServiceType,InvalidDeadline/);
+    await expect(locator.serviceError).toHaveText('Välj vilken tjänst det gäller.');
+    await expect(locator.deadlineError).toHaveText('Ange ett datum som inte har passerat.');
+    await expect(locator.successMessage).toBeHidden();
+  });
+
+  test('rejects a submission sent right after the page loaded (JS disabled)', async ({ page }) => {
+    await locator.serviceSelect.selectOption('pc_help');
+    await locator.termsCheckbox.setChecked(true);
+    await locator.messageInput.fill('Detta är ett giltigt testmeddelande.');
+    await locator.emailInput.fill('valid.user@example.com');
+    await locator.submitButton.click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(page).toHaveURL(/error=SubmittedTooFast/);
+    await expect(locator.generalError).toHaveText('Formuläret skickades för snabbt. Vänta några sekunder och försök igen.');
+  });
+
+  test('mails a notification about the inquiry through SMTP (JS disabled)', async ({ page }) => {
+    test.skip(!(await mailpitRunning()), 'start mailpit from the dev shell to test outgoing mail');
+
+    const message = `Meddelande via SMTP ${Date.now()} ${Math.random()}`;
+    await fillForm(locator, { message });
+    await locator.submitButton.click();
+    await page.waitForLoadState('domcontentloaded');
+    await expect(locator.successMessage).toBeVisible();
+
+    // NOTIFY_EMAIL in the dev shell
+    const mail = await waitForMail('kontakt@tivanderit.test', message);
+    expect(mail.subject).toMatch(/^Nytt ärende TIT-\d{6} från valid\.user@example\.com$/);
+    expect(mail.replyTo).toEqual(['valid.user@example.com']);
+    expect(mail.text).toContain(message);
+    expect(mail.text).toContain('Hjälp med persondatorer');
+  });
+
+  test.describe('Webhooks', () => {
+    // The dev shell points WEBHOOK_URLS at a receiver on a fixed port, so only one test at a
+    // time can listen
+    test.describe.configure({ mode: 'default' });
+
+    let server: Server | undefined;
+
+    test.beforeEach(({ browserName }) => {
+      test.skip(browserName !== 'chromium', 'the webhook receiver listens on a fixed port');
+    });
+
+    test.afterEach(async () => {
+      if (server) {
+        server.closeAllConnections();
+        await new Promise((resolve) => server!.close(resolve));
+        server = undefined;
+      }
+    });
+
+    /** Listens for deliveries of the inquiry with `message` and answers them with `status(attempt)`. */
+    function receiveWebhooks(message: string, count: number, status: (attempt: number) => number = () => 200) {
+      const received: { body: string; headers: IncomingHttpHeaders }[] = [];
+      return new Promise<typeof received>((resolve, reject) => {
+        server = createServer((request, response) => {
+          let body = '';
+          request.on('data', (chunk) => (body += chunk));
+          request.on('end', () => {
+            // Other tests submit the form at the same time
+            if (!body.includes(message)) {
+              response.end();
+              return;
+            }
+            received.push({ body, headers: request.headers });
+            response.statusCode = status(received.length);
+            response.end();
+            if (received.length === count) {
+              resolve(received);
+            }
+          });
+        });
+        server.once('error', reject);
+        server.listen(3002, '127.0.0.1');
+      });
+    }
+
+    test('posts a signed webhook for a stored inquiry (JS disabled)', async ({ page }) => {
+      const message = `Meddelande till webhook ${Date.now()}`;
+      const received = receiveWebhooks(message, 1);
+
+      await fillForm(locator, { message });
+      await locator.submitButton.click();
+      await page.waitForLoadState('domcontentloaded');
+      await expect(locator.successMessage).toBeVisible();
+
+      const [{ body, headers }] = await received;
+      const payload = JSON.parse(body);
+      expect(payload.event).toBe('inquiry.created');
+      expect(payload.data.reference).toMatch(/^TIT-\d{6}$/);
+      expect(payload.data.email).toBe('valid.user@example.com');
+      expect(payload.data.service_type).toBe('pc_help');
+      expect(headers['x-webhook-event']).toBe('inquiry.created');
+
+      const [, timestamp, signature] = /^t=(\d+),v1=([0-9a-f]+)$/.exec(String(headers['x-webhook-signature']))!;
+      const expected = createHmac('sha256', process.env.WEBHOOK_SECRET ?? '').update(`${timestamp}.${body}`).digest('hex');
+      expect(signature).toBe(expected);
+    });
+
+    test('retries a failed webhook with the same delivery id (JS disabled)', async ({ page }) => {
+      // JOB_RETRY_SECS is short in the dev shell, but workers only look for due retries now and then
+      test.setTimeout(60 * 1000);
+
+      const message = `Webhook som misslyckas först ${Date.now()}`;
+      const received = receiveWebhooks(message, 2, (attempt) => (attempt === 1 ? 500 : 200));
+
+      await fillForm(locator, { message });
+      await locator.submitButton.click();
+      await page.waitForLoadState('domcontentloaded');
+      await expect(locator.successMessage).toBeVisible();
+
+      const [first, retry] = await received;
+      expect(retry.body).toBe(first.body);
+      expect(retry.headers['x-webhook-id']).toMatch(/^[0-9a-f]{32}$/);
+      expect(retry.headers['x-webhook-id']).toBe(first.headers['x-webhook-id']);
+      // Signed again when it is sent again
+      expect(retry.headers['x-webhook-signature']).toMatch(/^t=\d+,v1=[0-9a-f]{64}$/);
+    });
+
+    test('keeps a webhook queued while the receiver is down (JS disabled)', async ({ page }) => {
+      test.setTimeout(60 * 1000);
+      const message = `Webhook till nedstängd mottagare ${Date.now()}`;
+
+      // Nothing listens yet, so the first attempts are refused
+      await fillForm(locator, { message });
+      await locator.submitButton.click();
+      await page.waitForLoadState('domcontentloaded');
+      await expect(locator.successMessage).toBeVisible();
+      await new Promise((resolve) => setTimeout(resolve, 1500));
+
+      const [{ body }] = await receiveWebhooks(message, 1);
+      expect(JSON.parse(body).event).toBe('inquiry.created');
+    });
+
+    test('gives up on a webhook after JOB_MAX_ATTEMPTS attempts (JS disabled)', async ({ page }) => {
+      test.setTimeout(90 * 1000);
+      const message = `Webhook som alltid misslyckas ${Date.now()}`;
+      // JOB_MAX_ATTEMPTS in the dev shell
+      const received = receiveWebhooks(message, 3, () => 500);
+
+      await fillForm(locator, { message });
+      await locator.submitButton.click();
+      await page.waitForLoadState('domcontentloaded');
+      await expect(locator.successMessage).toBeVisible();
+
+      const attempts = await received;
+      // A fourth attempt would be due within a few seconds, and the workers poll every five
+      await new Promise((resolve) => setTimeout(resolve, 15000));
+      expect(attempts).toHaveLength(3);
+      expect(new Set(attempts.map(({ headers }) => headers['x-webhook-id'])).size).toBe(1);
+    });
+  });
 });
@@ -0,0 +1,43 @@
+// Sets up what the admin inbox, LMTP and portal tests start from: a stored inquiry and an
+// admin session. Both need mailpit and the admin credentials from the dev shell.
+import { expect, type Page } from '@playwright/test';
+import { waitForMail } from './mailpit';
+
+// NOTIFY_EMAIL in the dev shell
+export const NOTIFY_EMAIL = 'kontakt@tivanderit.test';
+
+// CONTACT_FORM_MIN_FILL_SECS, the form sends a proof of work with JS enabled
+const MIN_FILL_MS = 3000;
+
+/** Whether the dev shell has set up an admin account to sign in with. */
+export function adminConfigured(): boolean {
+  return Boolean(process.env.ADMIN_USERNAME && process.env.ADMIN_PASSWORD);
+}
+
+/** Sends an inquiry through the contact form and returns its id, read from the notification mail. */
+export async function submitInquiry(page: Page, message: string, email: string): Promise<number> {
+  await page.goto('/#contact');
+  await expect(page.locator('#message')).toBeVisible();
+  const renderedAt = Date.now();
+
+  await page.locator('#service_type').selectOption('pc_help');
+  await page.locator('#terms').setChecked(true);
+  await page.locator('#message').fill(message);
+  await page.locator('#email').fill(email);
+  await page.waitForTimeout(Math.max(0, MIN_FILL_MS - (Date.now() - renderedAt)) + 100);
+  await page.getByTestId('contact-form-submit').click();
+  await expect(page.locator('.success-message')).toBeVisible();
+
+  const notification = await waitForMail(NOTIFY_EMAIL, message);
+  return Number(/TIT-(\d{6})/.exec(notification.subject)![1]);
+}
+
+/** Signs in to the admin inbox with ADMIN_USERNAME and ADMIN_PASSWORD from the dev shell. */
+export async function signInAsAdmin(page: Page) {
+  await page.goto('/admin/login');
+  await page.locator('#username').fill(process.env.ADMIN_USERNAME!);
+  await page.locator('#password').fill(process.env.ADMIN_PASSWORD!);
+  await page.getByRole('button', { name: 'Logga in' }).click();
+  await page.waitForLoadState('domcontentloaded');
+  await expect(page).toHaveURL(/\/admin$/);
+}
@@ -0,0 +1,159 @@
+import { test, expect } from '@playwright/test';
+import { connect, type Socket } from 'node:net';
+import { adminConfigured, signInAsAdmin, submitInquiry } from './inquiry';
+import { mailpitRunning, waitForMail } from './mailpit';
+
+// The dev shell starts the LMTP listener here (LMTP_ADDR)
+const LMTP_PORT = 2424;
+
+/** A minimal LMTP client: sends one line at a time and collects the reply lines. */
+class LmtpClient {
+  private buffer = '';
+  private waiting: (() => void) | null = null;
+
+  private constructor(private socket: Socket) {
+    socket.setEncoding('utf8');
+    socket.on('data', (chunk: string) => {
+      this.buffer += chunk;
+      this.waiting?.();
+    });
+  }
+
+  static async open(): Promise<LmtpClient> {
+    const socket = connect(LMTP_PORT, '127.0.0.1');
+    await new Promise<void>((resolve, reject) => socket.once('connect', resolve).once('error', reject));
+    return new LmtpClient(socket);
+  }
+
+  /** Waits for `count` complete replies (a reply ends with a line "NNN text"). */
+  async replies(count = 1): Promise<string[]> {
+    // The last element is an unfinished line (or empty)
+    const complete = () => this.buffer.split('\r\n').slice(0, -1).filter((line) => /^\d{3} /.test(line)).length >= count;
+    while (!complete()) {
+      await new Promise<void>((resolve) => (this.waiting = resolve));
+    }
+    const lines = this.buffer.split('\r\n').filter((line) => line !== '');
+    this.buffer = '';
+    return lines;
+  }
+
+  async send(line: string, replyCount = 1): Promise<string[]> {
+    this.socket.write(`${line}\r\n`);
+    return this.replies(replyCount);
+  }
+
+  close() {
+    this.socket.end();
+  }
+}
+
+/** Delivers `message` to `recipient` in one LMTP session and returns the reply to the data. */
+async function deliver(recipient: string, message: string[]): Promise<string[]> {
+  const client = await LmtpClient.open();
+  await client.replies();
+  await client.send('LHLO localhost');
+  await client.send('MAIL FROM:<kund@example.com>');
+  await client.send(`RCPT TO:<${recipient}>`);
+  await client.send('DATA');
+  const replies = await client.send([...message, '.'].join('\r\n'));
+  await client.send('QUIT');
+  client.close();
+  return replies;
+}
+
+test.describe('LMTP listener', () => {
+  test.beforeEach(({ browserName }) => {
+    test.skip(browserName !== 'chromium', 'no browser involved, run it once');
+  });
+
+  test('speaks LMTP and rejects mail that belongs to no inquiry', async () => {
+    const client = await LmtpClient.open();
+    expect((await client.replies())[0]).toMatch(/^220 /);
+
+    const lhlo = await client.send('LHLO localhost');
+    expect(lhlo[lhlo.length - 1]).toMatch(/^250 SIZE \d+$/);
+
+    expect(await client.send('MAIL FROM:<someone@example.com>')).toEqual(['250 2.1.0 OK']);
+    expect(await client.send('RCPT TO:<svar+1.00000000000000000000@tivanderit.test>')).toEqual(['250 2.1.5 OK']);
+    expect(await client.send('RCPT TO:<svar@tivanderit.test>')).toEqual(['250 2.1.5 OK']);
+    expect((await client.send('DATA'))[0]).toMatch(/^354 /);
+
+    const message = [
+      'From: someone@example.com',
+      'To: svar@tivanderit.test',
+      'Subject: Angående TIT-000001',
+      `Message-ID: <${Date.now()}@example.com>`,
+      '',
+      'Hej! En rad som börjar med punkt:',
+      '..punkt',
+      '.',
+    ].join('\r\n');
+    // One reply for every recipient: a forged token, and no token at all even though the subject
+    // has a reference number
+    expect(await client.send(message, 2)).toEqual(['550 5.1.1 No matching inquiry', '550 5.1.1 No matching inquiry']);
+
+    expect((await client.send('QUIT'))[0]).toMatch(/^221 /);
+    client.close();
+  });
+
+  test('wants LHLO before a transaction', async () => {
+    const client = await LmtpClient.open();
+    await client.replies();
+
+    expect(await client.send('MAIL FROM:<someone@example.com>')).toEqual(['503 5.5.1 Send LHLO first']);
+    expect(await client.send('HELO localhost')).toEqual(['500 5.5.2 Command not recognised']);
+
+    client.close();
+  });
+
+  test('adds an answer to our reply to the conversation, once', async ({ page }) => {
+    test.skip(!adminConfigured(), 'sign in with ADMIN_USERNAME and ADMIN_PASSWORD from the dev shell');
+    test.skip(!(await mailpitRunning()), 'start mailpit from the dev shell to test outgoing mail');
+    test.setTimeout(60 * 1000);
+
+    const email = `lmtp.${Date.now()}@example.com`;
+    const id = await submitInquiry(page, `Fråga som får svar via mejl ${Date.now()}`, email);
+    const reference = `TIT-${String(id).padStart(6, '0')}`;
+
+    // Reply from the admin inbox, the customer answers to its Reply-To
+    const reply = `Kan du skicka en skärmbild? ${Date.now()}`;
+    await signInAsAdmin(page);
+    await page.goto(`/admin/inquiries/${id}`);
+    await page.locator('#reply').fill(reply);
+    await page.getByRole('button', { name: 'Skicka svar' }).click();
+    await page.waitForLoadState('domcontentloaded');
+    const [replyAddress] = (await waitForMail(email, reply)).replyTo;
+
+    const answer = `Här kommer skärmbilden. ${Date.now()}`;
+    // Just the PNG signature, which is what the server looks at
+    const png = Buffer.from([0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d]);
+    const message = [
+      `From: ${email}`,
+      `To: ${replyAddress}`,
+      `Subject: Sv: Ditt ärende hos Tivander IT (${reference})`,
+      `Message-ID: <${Date.now()}.lmtp@example.com>`,
+      'MIME-Version: 1.0',
+      'Content-Type: multipart/mixed; boundary="grans"',
+      '',
+      '--grans',
+      'Content-Type: text/plain; charset=utf-8',
+      '',
+      answer,
+      '--grans',
+      'Content-Type: image/png; name="skarmbild.png"',
+      'Content-Disposition: attachment; filename="skarmbild.png"',
+      'Content-Transfer-Encoding: base64',
+      '',
+      png.toString('base64'),
+      '--grans--',
+    ];
+
+    expect(await deliver(replyAddress, message)).toEqual([`250 2.0.0 Added to ${reference}`]);
+    // The mail server delivers it again, e.g. after a lost connection
+    expect(await deliver(replyAddress, message)).toEqual([`250 2.0.0 Already added to ${reference}`]);
+
+    await page.reload();
+    await expect(page.locator('.admin-thread li.incoming').filter({ hasText: answer })).toHaveCount(1);
+    await expect(page.locator('.admin-attachments a', { hasText: 'skarmbild.png' })).toHaveCount(1);
+  });
+});
@@ -0,0 +1,42 @@
+// Reads the mail the server has sent from the mailpit SMTP sink in the dev shell, which the dev
+// shell points SMTP_URL at. Mail is only sent by a server built with `--bin-features ssr,mail`.
+const MAILPIT_URL = process.env.MAILPIT_URL ?? 'http://127.0.0.1:8025';
+
+export type Mail = {
+  subject: string;
+  to: string[];
+  replyTo: string[];
+  text: string;
+};
+
+type Address = { Address: string };
+
+/** Whether mailpit is running, tests that need mail are skipped without it. */
+export async function mailpitRunning(): Promise<boolean> {
+  try {
+    return (await fetch(`${MAILPIT_URL}/api/v1/info`)).ok;
+  } catch {
+    return false;
+  }
+}
+
+/** Waits for the first mail to `to` that contains `text` anywhere, mail is sent by a background job. */
+export async function waitForMail(to: string, text: string, timeoutMs = 20000): Promise<Mail> {
+  const query = encodeURIComponent(`to:${to} "${text}"`);
+  const deadline = Date.now() + timeoutMs;
+
+  while (Date.now() < deadline) {
+    const { messages } = await (await fetch(`${MAILPIT_URL}/api/v1/search?query=${query}`)).json();
+    if (messages.length > 0) {
+      const message = await (await fetch(`${MAILPIT_URL}/api/v1/message/${messages[0].ID}`)).json();
+      return {
+        subject: message.Subject,
+        to: message.To.map((address: Address) => address.Address),
+        replyTo: message.ReplyTo.map((address: Address) => address.Address),
+        text: message.Text,
+      };
+    }
+    await new Promise((resolve) => setTimeout(resolve, 500));
+  }
+  throw new Error(`No mail to ${to} containing "${text}" within ${timeoutMs} ms`);
+}
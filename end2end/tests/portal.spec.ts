@@ -0,0 +1,130 @@
+import { test, expect, type Page } from '@playwright/test';
+import { adminConfigured, signInAsAdmin, submitInquiry } from './inquiry';
+import { mailpitRunning, waitForMail } from './mailpit';
+
+/** Asks for a sign-in link for `email` and returns it, read from the mail. */
+async function requestLink(page: Page, email: string): Promise<string> {
+  await page.goto('/portal');
+  await page.locator('#portal-email').fill(email);
+  await page.getByRole('button', { name: 'Skicka länk' }).click();
+  await page.waitForLoadState('domcontentloaded');
+  await expect(page).toHaveURL(/status=sent/);
+
+  const mail = await waitForMail(email, 'Visa mina ärenden');
+  return /http\S+\/portal\/login\?token=\S+/.exec(mail.text)![0];
+}
+
+async function followLink(page: Page, link: string) {
+  await page.goto(link);
+  await page.getByRole('button', { name: 'Visa mina ärenden' }).click();
+  await page.waitForLoadState('domcontentloaded');
+}
+
+test.describe('Customer portal', () => {
+  test('asks for an address when not signed in', async ({ page }) => {
+    await page.goto('/portal');
+
+    await expect(page.getByRole('heading', { name: 'Mina ärenden' })).toBeVisible();
+    await expect(page.locator('#portal-email')).toBeVisible();
+  });
+
+  test('gives the same answer for an address without inquiries', async ({ page }) => {
+    await page.goto('/portal');
+
+    await page.locator('#portal-email').fill(`nobody-${Date.now()}@example.com`);
+    await page.getByRole('button', { name: 'Skicka länk' }).click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(page).toHaveURL(/status=sent/);
+    await expect(page.locator('#portal p.success-message')).toContainText('Om vi har ärenden från adressen');
+  });
+
+  test('only exports data for a signed in customer', async ({ request }) => {
+    const response = await request.get('/portal/export');
+
+    expect(response.status()).toBe(401);
+  });
+
+  test('rejects a link with a wrong signature', async ({ page }) => {
+    await page.goto(`/portal/login?token=${'0'.repeat(64)}&sig=${'0'.repeat(64)}`);
+    await page.getByRole('button', { name: 'Visa mina ärenden' }).click();
+
+    await page.waitForLoadState('domcontentloaded');
+    await expect(page).toHaveURL(/error=InvalidLink/);
+    await expect(page.locator('#portal p.error-message')).toContainText('Länken är ogiltig');
+  });
+
+  test.describe('Signed in', () => {
+    test.beforeEach(async () => {
+      test.skip(!(await mailpitRunning()), 'start mailpit from the dev shell to test outgoing mail');
+    });
+
+    test('signs in with a mailed link that only works once', async ({ page, browser, browserName }) => {
+      test.setTimeout(60 * 1000);
+      const email = `portal.${browserName}.${Date.now()}@example.com`;
+      const message = `Fråga att följa i portalen ${Date.now()}`;
+      const id = await submitInquiry(page, message, email);
+
+      const link = await requestLink(page, email);
+      await followLink(page, link);
+
+      await expect(page).toHaveURL(/\/portal$/);
+      const inquiry = page.locator('section.portal-inquiry').filter({ hasText: message });
+      await expect(inquiry.locator('h2')).toHaveText(`TIT-${String(id).padStart(6, '0')}`);
+      await expect(inquiry.locator('.portal-thread li.incoming .portal-message')).toHaveText(message);
+
+      // Someone else who gets hold of the link afterwards
+      const other = await browser.newPage();
+      await followLink(other, link);
+      await expect(other).toHaveURL(/error=LinkExpired/);
+      await expect(other.locator('#portal p.error-message')).toContainText('Länken har redan använts');
+      await expect(other.locator('section.portal-inquiry')).toHaveCount(0);
+      await other.close();
+    });
+
+    test('exports everything stored about the address as JSON', async ({ page, browserName }) => {
+      test.setTimeout(60 * 1000);
+      const email = `export.${browserName}.${Date.now()}@example.com`;
+      const message = `Fråga att exportera ${Date.now()}`;
+      const id = await submitInquiry(page, message, email);
+      await followLink(page, await requestLink(page, email));
+
+      // Shares the session cookie with the page
+      const response = await page.request.get('/portal/export');
+      expect(response.status()).toBe(200);
+      expect(response.headers()['content-disposition']).toContain('attachment');
+
+      const data = await response.json();
+      expect(data.email).toBe(email);
+      expect(data.inquiries).toHaveLength(1);
+      const [inquiry] = data.inquiries;
+      expect(inquiry.id).toBe(id);
+      expect(inquiry.reference).toBe(`TIT-${String(id).padStart(6, '0')}`);
+      expect(inquiry.email).toBe(email);
+      expect(inquiry.message).toBe(message);
+      expect(inquiry.service_type).toBe('pc_help');
+      expect(inquiry.consent.text).toBeTruthy();
+      expect(inquiry.messages).toEqual([]);
+      expect(data.bookings).toEqual([]);
+    });
+
+    test('erases the inquiries of the address and signs out', async ({ page, browserName }) => {
+      test.skip(!adminConfigured(), 'the admin inbox shows that the inquiry is gone');
+      test.setTimeout(60 * 1000);
+      const email = `radera.${browserName}.${Date.now()}@example.com`;
+      const id = await submitInquiry(page, `Fråga att radera ${Date.now()}`, email);
+      await followLink(page, await requestLink(page, email));
+
+      await page.locator('#portal-erase-confirm').setChecked(true);
+      await page.getByRole('button', { name: 'Radera mina uppgifter' }).click();
+      await page.waitForLoadState('domcontentloaded');
+      await expect(page).toHaveURL(/status=erased/);
+      await expect(page.locator('#portal p.success-message')).toHaveText('Dina uppgifter har raderats.');
+      expect((await page.request.get('/portal/export')).status()).toBe(401);
+
+      await signInAsAdmin(page);
+      await page.goto(`/admin/inquiries/${id}`);
+      await expect(page.locator('p.error-message')).toHaveText('Ärendet kunde inte hittas.');
+    });
+  });
+});
@@ -48,6 +48,11 @@
               default = "/var/lib/tivanderit-web/tivanderit.db";
               description = "Path to the SQLite database file.";
             };
+            environmentFile = mkOption {
+              type = types.nullOr types.path;
+              default = null;
+              description = "File with additional environment variables, e.g. ADMIN_USERNAME, ADMIN_PASSWORD_HASH, ENCRYPTION_KEY and ENCRYPTION_INDEX_KEY.";
+            };
           };
 
           # Service configuration
@@ -79,6 +84,8 @@
                 ProtectHome = "true";
                 PrivateTmp = true;
                 NoNewPrivileges = true;
+              } // optionalAttrs (cfg.environmentFile != null) {
+                EnvironmentFile = cfg.environmentFile;
               };
 
               # Environment variables needed by the running service
@@ -112,7 +119,7 @@
         projectVersion = cargoTomlParsed.package.version or "0.0.1";
 
         # --- Toolchain and Dependencies ---
-        rustVersion = pkgs.rust-bin.stable."1.87.0".default.override {
+        rustVersion = pkgs.rust-bin.stable."1.89.0".default.override {
           extensions = [ "rust-src" ];
           targets = [ "wasm32-unknown-unknown" ];
         };
@@ -165,7 +172,7 @@
             runHook preBuild
             export PATH="${pkgs.lib.makeBinPath buildDeps}:${rustVersion}/bin:$PATH"
             echo "Running cargo leptos build --release..."
-            cargo leptos build --release
+            cargo leptos build --release --bin-features ssr,mail
             runHook postBuild
           '';
 
@@ -218,6 +225,8 @@
             sqlx-cli
             leptosfmt
             sqlite-interactive
+            libargon2
+            mailpit
             git
             nodejs
             playwright-test
@@ -237,6 +246,32 @@
             export LEPTOS_RELOAD_PORT="3001"
             export DATABASE_URL="sqlite:tivanderit-dev.db"
             export MIGRATIONS_PATH="./migrations"
+            # Links in booking mails point here
+            export SITE_URL="http://127.0.0.1:3000"
+            # Inquiries are stored encrypted, this key is for development only
+            export ENCRYPTION_KEY="a0a1a2a3a4a5a6a7a8a9aaabacadaeafb0b1b2b3b4b5b6b7b8b9babbbcbdbebf"
+            export ENCRYPTION_INDEX_KEY="c0c1c2c3c4c5c6c7c8c9cacbcccdcecfd0d1d2d3d4d5d6d7d8d9dadbdcdddedf"
+            # The e2e tests submit the contact form many times from the same address
+            export RATE_LIMIT_BURST="1000"
+            export ADMIN_LOGIN_BURST="1000"
+            export BOOKING_CLIENT_MAX_PENDING="1000"
+            # Outgoing mail goes to mailpit (run `mailpit`), with the `mail` feature enabled
+            export SMTP_URL="smtp://127.0.0.1:1025"
+            export MAIL_FROM="Tivander IT <hej@tivanderit.test>"
+            export NOTIFY_EMAIL="kontakt@tivanderit.test"
+            # The e2e tests receive webhooks here
+            export WEBHOOK_URLS="http://127.0.0.1:3002/webhook"
+            export WEBHOOK_SECRET="development-webhook-secret"
+            # The e2e tests wait for a failed webhook to be retried, and for it to be given up on
+            export JOB_RETRY_SECS="1"
+            export JOB_MAX_ATTEMPTS="3"
+            # An admin account for development, the e2e tests sign in with it
+            export ADMIN_USERNAME="admin"
+            export ADMIN_PASSWORD="development-password"
+            export ADMIN_PASSWORD_HASH="$(echo -n "$ADMIN_PASSWORD" | argon2 tivanderit-development -id -e)"
+            # Answers to replies, the e2e tests talk LMTP to it directly
+            export LMTP_ADDR="127.0.0.1:2424"
+            export REPLY_ADDRESS="svar@tivanderit.test"
             export PLAYWRIGHT_BROWSERS_PATH=${pkgs.playwright-driver.browsers}
             export PLAYWRIGHT_SKIP_VALIDATE_HOST_REQUIREMENTS=true
 
@@ -304,6 +339,13 @@
 
               # Activate Tivander IT Web service.
               services.tivanderit-web.enable = true;
+              # Caddy below forwards the client address
+              systemd.services.tivanderit-web.environment.TRUSTED_PROXIES = "127.0.0.1,::1";
+              # A fixed key is fine for the test VM, real deployments set it in environmentFile
+              systemd.services.tivanderit-web.environment.ENCRYPTION_KEY =
+                "0000000000000000000000000000000000000000000000000000000000000000";
+              systemd.services.tivanderit-web.environment.ENCRYPTION_INDEX_KEY =
+                "1111111111111111111111111111111111111111111111111111111111111111";
 
               # Activate Caddy service
               services.caddy = {
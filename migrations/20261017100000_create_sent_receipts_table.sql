@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS sent_receipts
+(
+    id          INTEGER PRIMARY KEY,
+    email_id    INTEGER NOT NULL,
+    recipient   TEXT NOT NULL,
+    sent_at     DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS sent_receipts_recipient_idx ON sent_receipts (recipient, sent_at);
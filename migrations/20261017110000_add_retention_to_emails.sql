@@ -0,0 +1,12 @@
+ALTER TABLE emails ADD COLUMN finished_at DATETIME;
+ALTER TABLE emails ADD COLUMN anonymised_at DATETIME;
+
+CREATE TABLE IF NOT EXISTS retention_runs
+(
+    id             INTEGER PRIMARY KEY,
+    started_at     DATETIME DEFAULT CURRENT_TIMESTAMP,
+    mode           TEXT NOT NULL,
+    grace_days     INTEGER NOT NULL,
+    affected_rows  INTEGER NOT NULL DEFAULT 0,
+    error          TEXT
+);
@@ -0,0 +1,17 @@
+ALTER TABLE emails ADD COLUMN status TEXT NOT NULL DEFAULT 'new';
+
+-- Inquiries that were already marked as finished count as closed
+UPDATE emails SET status = 'closed' WHERE finished_at IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS status_changes
+(
+    id           INTEGER PRIMARY KEY,
+    email_id     INTEGER NOT NULL,
+    from_status  TEXT NOT NULL,
+    to_status    TEXT NOT NULL,
+    actor        TEXT NOT NULL,
+    changed_at   DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS emails_status_idx ON emails (status);
+CREATE INDEX IF NOT EXISTS status_changes_email_id_idx ON status_changes (email_id);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS used_pow_challenges
+(
+    challenge  TEXT PRIMARY KEY,
+    used_at    DATETIME DEFAULT CURRENT_TIMESTAMP
+);
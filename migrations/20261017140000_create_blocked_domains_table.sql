@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS blocked_domains
+(
+    domain      TEXT PRIMARY KEY,
+    reason      TEXT,
+    added_by    TEXT NOT NULL,
+    created_at  DATETIME DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS attachments
+(
+    id            INTEGER PRIMARY KEY,
+    email_id      INTEGER NOT NULL,
+    filename      TEXT NOT NULL,
+    content_type  TEXT NOT NULL,
+    size          INTEGER NOT NULL,
+    data          BLOB NOT NULL,
+    created_at    DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS attachments_email_id_idx ON attachments (email_id);
@@ -0,0 +1,7 @@
+-- NULL for inquiries sent before the form asked for a service
+ALTER TABLE emails ADD COLUMN service_type TEXT CHECK (service_type IN ('software_development', 'pc_help'));
+ALTER TABLE emails ADD COLUMN budget TEXT CHECK (budget IN ('under_10k', '10k_50k', '50k_150k', 'over_150k'));
+ALTER TABLE emails ADD COLUMN deadline DATE;
+ALTER TABLE emails ADD COLUMN company TEXT;
+
+CREATE INDEX IF NOT EXISTS emails_service_type_idx ON emails (service_type, deadline);
@@ -0,0 +1,40 @@
+-- Weekly opening hours for PC technician appointments, ISO weekdays (1 = Monday)
+CREATE TABLE IF NOT EXISTS booking_hours
+(
+    id          INTEGER PRIMARY KEY,
+    weekday     INTEGER NOT NULL CHECK (weekday BETWEEN 1 AND 7),
+    start_time  TEXT NOT NULL,
+    end_time    TEXT NOT NULL
+);
+
+INSERT INTO booking_hours (weekday, start_time, end_time) VALUES
+    (1, '09:00', '17:00'),
+    (2, '09:00', '17:00'),
+    (3, '09:00', '17:00'),
+    (4, '09:00', '17:00'),
+    (5, '09:00', '15:00');
+
+-- Days without appointments, e.g. holidays and vacations
+CREATE TABLE IF NOT EXISTS booking_closed_dates
+(
+    date        DATE PRIMARY KEY,
+    reason      TEXT,
+    added_by    TEXT NOT NULL,
+    created_at  DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS bookings
+(
+    id            INTEGER PRIMARY KEY,
+    slot_start    DATETIME NOT NULL,
+    email         TEXT NOT NULL,
+    description   TEXT NOT NULL,
+    status        TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'confirmed', 'cancelled')),
+    created_at    DATETIME DEFAULT CURRENT_TIMESTAMP,
+    confirmed_at  DATETIME,
+    cancelled_at  DATETIME
+);
+
+-- A slot can only be held by one booking that isn't cancelled, this is what makes reservations atomic
+CREATE UNIQUE INDEX IF NOT EXISTS bookings_active_slot_idx ON bookings (slot_start) WHERE status != 'cancelled';
+CREATE INDEX IF NOT EXISTS bookings_status_idx ON bookings (status);
@@ -0,0 +1,5 @@
+ALTER TABLE emails ADD COLUMN idempotency_key TEXT;
+
+-- A retried submission carries the same key, the index makes sure it is only stored once
+CREATE UNIQUE INDEX IF NOT EXISTS emails_idempotency_key_idx ON emails (idempotency_key) WHERE idempotency_key IS NOT NULL;
+CREATE INDEX IF NOT EXISTS emails_email_creation_date_idx ON emails (email, creation_date);
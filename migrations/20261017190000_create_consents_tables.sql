@@ -0,0 +1,26 @@
+-- Every version of the terms that has been shown next to a checkbox. Rows are added by the
+-- server from the texts in src/terms.rs and never changed.
+CREATE TABLE IF NOT EXISTS terms_versions
+(
+    id          INTEGER PRIMARY KEY,
+    form        TEXT NOT NULL CHECK (form IN ('contact', 'booking')),
+    version     INTEGER NOT NULL,
+    text        TEXT NOT NULL,
+    created_at  DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (form, version)
+);
+
+CREATE TABLE IF NOT EXISTS consents
+(
+    id                INTEGER PRIMARY KEY,
+    terms_version_id  INTEGER NOT NULL REFERENCES terms_versions (id),
+    email_id          INTEGER,
+    booking_id        INTEGER,
+    -- Keyed hash, the address itself is not stored
+    ip_hash           TEXT,
+    consented_at      DATETIME DEFAULT CURRENT_TIMESTAMP,
+    CHECK ((email_id IS NULL) != (booking_id IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS consents_email_id_idx ON consents (email_id);
+CREATE INDEX IF NOT EXISTS consents_booking_id_idx ON consents (booking_id);
@@ -0,0 +1,10 @@
+-- email and message are encrypted by the server (see src/crypto.rs), rows stored in plain text
+-- are encrypted at startup. The hashes stand in for the columns in lookups.
+ALTER TABLE emails ADD COLUMN email_hash TEXT;
+ALTER TABLE emails ADD COLUMN content_hash TEXT;
+
+CREATE INDEX IF NOT EXISTS emails_content_hash_idx ON emails (content_hash, creation_date);
+DROP INDEX IF EXISTS emails_email_creation_date_idx;
+
+-- Receipts are throttled by a hash of the address from now on
+UPDATE sent_receipts SET recipient = '';
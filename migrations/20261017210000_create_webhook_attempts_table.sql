@@ -0,0 +1,16 @@
+-- One row per attempt to deliver a webhook, attempts of the same delivery share delivery_id
+CREATE TABLE IF NOT EXISTS webhook_attempts
+(
+    id            INTEGER PRIMARY KEY,
+    delivery_id   TEXT NOT NULL,
+    email_id      INTEGER NOT NULL,
+    event         TEXT NOT NULL,
+    url           TEXT NOT NULL,
+    attempt       INTEGER NOT NULL,
+    attempted_at  DATETIME DEFAULT CURRENT_TIMESTAMP,
+    status_code   INTEGER,
+    error         TEXT
+);
+
+CREATE INDEX IF NOT EXISTS webhook_attempts_email_id_idx ON webhook_attempts (email_id);
+CREATE INDEX IF NOT EXISTS webhook_attempts_delivery_id_idx ON webhook_attempts (delivery_id);
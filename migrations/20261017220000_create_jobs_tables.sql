@@ -0,0 +1,33 @@
+-- Side effects (mail, webhooks) waiting to be run, see src/jobs.rs. The payload is encrypted.
+CREATE TABLE IF NOT EXISTS jobs
+(
+    id            INTEGER PRIMARY KEY,
+    kind          TEXT NOT NULL,
+    email_id      INTEGER,
+    payload       TEXT NOT NULL,
+    attempts      INTEGER NOT NULL DEFAULT 0,
+    max_attempts  INTEGER NOT NULL,
+    run_at        DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    locked_until  DATETIME,
+    last_error    TEXT,
+    created_at    DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS jobs_run_at_idx ON jobs (run_at);
+CREATE INDEX IF NOT EXISTS jobs_email_id_idx ON jobs (email_id);
+
+-- Jobs that failed for good
+CREATE TABLE IF NOT EXISTS dead_jobs
+(
+    id          INTEGER PRIMARY KEY,
+    job_id      INTEGER NOT NULL,
+    kind        TEXT NOT NULL,
+    email_id    INTEGER,
+    payload     TEXT NOT NULL,
+    attempts    INTEGER NOT NULL,
+    last_error  TEXT,
+    created_at  DATETIME,
+    failed_at   DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS dead_jobs_email_id_idx ON dead_jobs (email_id);
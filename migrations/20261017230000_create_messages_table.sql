@@ -0,0 +1,14 @@
+-- Replies to an inquiry and answers from the customer, the inquiry itself is the first message
+CREATE TABLE IF NOT EXISTS messages
+(
+    id          INTEGER PRIMARY KEY,
+    email_id    INTEGER NOT NULL,
+    direction   TEXT NOT NULL,
+    -- Encrypted like emails.message
+    body        TEXT NOT NULL,
+    -- The admin who sent an outgoing message
+    author      TEXT,
+    created_at  DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS messages_email_id_idx ON messages (email_id, created_at);
@@ -0,0 +1,4 @@
+-- The Message-ID of a mail from a customer, so that a mail delivered twice is only stored once
+ALTER TABLE messages ADD COLUMN mail_message_id TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS messages_mail_message_id_idx ON messages (email_id, mail_message_id) WHERE mail_message_id IS NOT NULL;
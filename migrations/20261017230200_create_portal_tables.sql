@@ -0,0 +1,24 @@
+-- Sign-in links for the customer portal, mailed to the address an inquiry was sent from.
+-- Like admin sessions, tokens are only stored hashed.
+CREATE TABLE IF NOT EXISTS portal_links
+(
+    id          INTEGER PRIMARY KEY,
+    token_hash  TEXT NOT NULL UNIQUE,
+    -- The keyed hash of the address, as in emails.email_hash
+    email_hash  TEXT NOT NULL,
+    created_at  DATETIME DEFAULT CURRENT_TIMESTAMP,
+    expires_at  DATETIME NOT NULL,
+    -- A link signs in once
+    used_at     DATETIME
+);
+
+CREATE INDEX IF NOT EXISTS portal_links_email_hash_idx ON portal_links (email_hash, created_at);
+
+CREATE TABLE IF NOT EXISTS portal_sessions
+(
+    id          INTEGER PRIMARY KEY,
+    token_hash  TEXT NOT NULL UNIQUE,
+    email_hash  TEXT NOT NULL,
+    created_at  DATETIME DEFAULT CURRENT_TIMESTAMP,
+    expires_at  DATETIME NOT NULL
+);
@@ -0,0 +1,16 @@
+-- Exports and erasures that people have made of their own data through the portal. Only the
+-- keyed hash of the address is kept, so the record outlives an erasure without the address.
+CREATE TABLE IF NOT EXISTS data_requests
+(
+    id            INTEGER PRIMARY KEY,
+    action        TEXT NOT NULL CHECK (action IN ('export', 'erasure')),
+    email_hash    TEXT NOT NULL,
+    -- What the export contained or the erasure removed
+    inquiries     INTEGER NOT NULL DEFAULT 0,
+    bookings      INTEGER NOT NULL DEFAULT 0,
+    -- Keyed hash like consents.ip_hash
+    ip_hash       TEXT,
+    requested_at  DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS data_requests_email_hash_idx ON data_requests (email_hash);
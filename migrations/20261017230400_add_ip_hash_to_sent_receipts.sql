@@ -0,0 +1,4 @@
+-- Receipts are also throttled per client, keyed like consents.ip_hash
+ALTER TABLE sent_receipts ADD COLUMN ip_hash TEXT;
+
+CREATE INDEX IF NOT EXISTS sent_receipts_ip_hash_idx ON sent_receipts (ip_hash, sent_at);
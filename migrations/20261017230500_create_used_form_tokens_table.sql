@@ -0,0 +1,7 @@
+-- Form tokens that have been submitted, so that a rendered form can't be posted over and over.
+-- Kept until the token would have expired anyway.
+CREATE TABLE IF NOT EXISTS used_form_tokens
+(
+    token    TEXT PRIMARY KEY,
+    used_at  DATETIME DEFAULT CURRENT_TIMESTAMP
+);
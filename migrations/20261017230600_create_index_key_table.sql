@@ -0,0 +1,7 @@
+-- The id of the ENCRYPTION_INDEX_KEY that the stored hashes were made with, so that they are
+-- recomputed once when it is first set
+CREATE TABLE IF NOT EXISTS index_key
+(
+    id      INTEGER PRIMARY KEY CHECK (id = 1),
+    key_id  TEXT NOT NULL
+);
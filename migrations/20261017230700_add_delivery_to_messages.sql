@@ -0,0 +1,9 @@
+-- Whether a reply from the admin inbox has been mailed: 'queued', 'sent' or 'failed'. NULL for
+-- answers from the customer and replies from before this was recorded.
+ALTER TABLE messages ADD COLUMN delivery_status TEXT;
+-- The last error from mailing the reply
+ALTER TABLE messages ADD COLUMN delivery_error TEXT;
+-- The job mailing the reply, cleared when it is done since job ids can be reused
+ALTER TABLE messages ADD COLUMN job_id INTEGER;
+
+CREATE INDEX IF NOT EXISTS messages_job_id_idx ON messages (job_id) WHERE job_id IS NOT NULL;
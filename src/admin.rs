@@ -0,0 +1,1213 @@
+use leptos::prelude::*;
+use leptos_router::{
+    components::{Outlet, Redirect},
+    hooks::{use_params_map, use_query_map},
+};
+use crate::booking::{slot_label, Booking, BookingStatus};
+use crate::error::AdminError;
+use crate::terms::Consent;
+use crate::inquiry::{BudgetRange, InquiryStatus, MessageDirection, ServiceType, StatusChange, ThreadMessage};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+pub const INQUIRIES_PER_PAGE: i64 = 20;
+
+/// The columns of `emails` that make up an `Inquiry`.
+#[cfg(feature = "ssr")]
+pub(crate) const INQUIRY_COLUMNS: &str =
+    "id, email, message, creation_date, status, finished_at, anonymised_at, service_type, budget, deadline, company";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct Inquiry {
+    pub id: i64,
+    pub email: String,
+    pub message: String,
+    pub creation_date: String,
+    pub status: InquiryStatus,
+    pub finished_at: Option<String>,
+    pub anonymised_at: Option<String>,
+    /// `None` for inquiries sent before the form asked for it.
+    pub service_type: Option<ServiceType>,
+    pub budget: Option<BudgetRange>,
+    pub deadline: Option<String>,
+    pub company: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl Inquiry {
+    /// `email` and `message` are stored encrypted, this is where they are decrypted for the admin
+    /// pages and for data exports.
+    pub(crate) fn decrypted(self) -> Inquiry {
+        use crate::crypto::{decrypt_or_placeholder, Field};
+
+        Inquiry {
+            email: decrypt_or_placeholder(Field::Email, &self.email),
+            message: decrypt_or_placeholder(Field::Message, &self.message),
+            ..self
+        }
+    }
+}
+
+/// An attachment without its content, which is downloaded from `/admin/attachments/{id}`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct AttachmentInfo {
+    pub id: i64,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+}
+
+/// A domain blocked from the contact form by an admin. The bundled list isn't shown.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct BlockedDomain {
+    pub domain: String,
+    pub reason: Option<String>,
+    pub added_by: String,
+    pub created_at: String,
+}
+
+/// A day without bookable slots.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct ClosedDate {
+    pub date: String,
+    pub reason: Option<String>,
+    pub added_by: String,
+    pub created_at: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InquiryPage {
+    pub inquiries: Vec<Inquiry>,
+    pub filter: String,
+    /// A service type, or empty for all.
+    pub service: String,
+    /// `deadline` sorts by desired completion date, anything else by date received.
+    pub sort: String,
+    pub page: i64,
+    pub total_pages: i64,
+    pub total: i64,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use crate::app::ssr::db;
+    use crate::error::AdminError;
+    use argon2::{Argon2, PasswordHash, PasswordVerifier};
+    use axum::http::{header, HeaderMap, HeaderValue};
+    use leptos::prelude::*;
+    use leptos_axum::{extract, ResponseOptions};
+    use rand::RngCore;
+    use sha2::{Digest, Sha256};
+
+    pub const SESSION_COOKIE: &str = "admin_session";
+    pub const SESSION_HOURS: i64 = 8;
+
+    /// Generates a random, hex encoded session token.
+    pub fn new_token() -> String {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        hex::encode(bytes)
+    }
+
+    /// Tokens are only stored hashed, so a leaked database can't be used to hijack a session.
+    pub fn hash_token(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    pub fn cookie_value(headers: &HeaderMap, name: &str) -> Option<String> {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.to_string())
+    }
+
+    /// Sets (or clears, with `max_age_secs` = 0) a session cookie on the current response.
+    pub fn set_cookie(name: &str, value: &str, max_age_secs: i64) {
+        // Allow plain http in development
+        let secure = if cfg!(debug_assertions) { "" } else { "; Secure" };
+        let cookie = format!("{name}={value}; Path=/; HttpOnly; SameSite=Strict; Max-Age={max_age_secs}{secure}");
+
+        if let (Some(res), Ok(value)) = (use_context::<ResponseOptions>(), HeaderValue::from_str(&cookie)) {
+            res.append_header(header::SET_COOKIE, value);
+        }
+    }
+
+    /// Checks the credentials against `ADMIN_USERNAME` and `ADMIN_PASSWORD_HASH` (an argon2 PHC string).
+    pub fn verify_credentials(username: &str, password: &str) -> bool {
+        let (Ok(expected_username), Ok(password_hash)) = (std::env::var("ADMIN_USERNAME"), std::env::var("ADMIN_PASSWORD_HASH")) else {
+            leptos::logging::log!("ADMIN_USERNAME or ADMIN_PASSWORD_HASH is not set - admin login is disabled.");
+            return false;
+        };
+        let Ok(parsed_hash) = PasswordHash::new(&password_hash) else {
+            leptos::logging::log!("ADMIN_PASSWORD_HASH is not a valid argon2 hash - admin login is disabled.");
+            return false;
+        };
+
+        // Always verify the password, so that a wrong username takes as long as a wrong password.
+        let password_ok = Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok();
+        password_ok && username == expected_username
+    }
+
+    /// Returns the username of the logged in admin, or `NotAuthenticated`.
+    pub async fn require_admin() -> Result<String, AdminError> {
+        let headers: HeaderMap = extract().await.map_err(|_| AdminError::NotAuthenticated)?;
+        let token = cookie_value(&headers, SESSION_COOKIE).ok_or(AdminError::NotAuthenticated)?;
+        admin_for_token(&token).await
+    }
+
+    /// Returns the username for a session token, for handlers outside of server functions.
+    pub async fn admin_for_token(token: &str) -> Result<String, AdminError> {
+        let mut conn = db().await?;
+        let username: Option<String> = sqlx::query_scalar(
+            "SELECT username FROM admin_sessions WHERE token_hash = $1 AND expires_at > CURRENT_TIMESTAMP",
+        )
+        .bind(hash_token(token))
+        .fetch_optional(&mut conn)
+        .await?;
+
+        username.ok_or(AdminError::NotAuthenticated)
+    }
+}
+
+/// Renders server errors for admin pages, sending unauthenticated users to the login page.
+fn admin_error_view(error: AdminError) -> AnyView {
+    match error {
+        AdminError::NotAuthenticated => view! { <Redirect path="/admin/login"/> }.into_any(),
+        e => view! { <p class="error-message">{e.get_user_message()}</p> }.into_any(),
+    }
+}
+
+#[component]
+pub fn AdminLoginPage() -> impl IntoView {
+    let login_action = ServerAction::<AdminLogin>::new();
+    let query_map = use_query_map();
+
+    let error = query_map.with(|params| {
+        params.get("error").and_then(|error_str| AdminError::from_str(&error_str).ok())
+    });
+
+    view! {
+        <div id="admin" class="admin-login">
+            <h1>"Logga in"</h1>
+            {error.map(|e| view! { <p class="error-message">{e.get_user_message()}</p> })}
+            <ActionForm action=login_action>
+                <div class="form-field">
+                    <label for="username">"Användarnamn:"</label>
+                    <input id="username" type="text" name="username" autocomplete="username" required/>
+                </div>
+                <div class="form-field">
+                    <label for="password">"Lösenord:"</label>
+                    <input id="password" type="password" name="password" autocomplete="current-password" required/>
+                </div>
+                <input type="submit" value="Logga in"/>
+            </ActionForm>
+        </div>
+    }
+}
+
+#[component]
+pub fn AdminLayout() -> impl IntoView {
+    let logout_action = ServerAction::<AdminLogout>::new();
+
+    view! {
+        <div id="admin">
+            <div class="admin-header">
+                <a href="/admin"><h1>"Inkorg"</h1></a>
+                <a href="/admin/bookings">"Bokningar"</a>
+                <a href="/admin/blocklist">"Blockerade domäner"</a>
+                <ActionForm action=logout_action>
+                    <input type="submit" value="Logga ut"/>
+                </ActionForm>
+            </div>
+            <Outlet/>
+        </div>
+    }
+}
+
+#[component]
+pub fn AdminInboxPage() -> impl IntoView {
+    let query_map = use_query_map();
+    let (page, filter, service, sort) = query_map.with(|params| {
+        (
+            params.get("page").and_then(|p| p.parse::<i64>().ok()).unwrap_or(1),
+            // Open inquiries are what we work with, so that's the default
+            params.get("status").unwrap_or_else(|| "open".to_string()),
+            params.get("service").unwrap_or_default(),
+            params.get("sort").unwrap_or_default(),
+        )
+    });
+
+    let inquiries = Resource::new(
+        move || (page, filter.clone(), service.clone(), sort.clone()),
+        |(page, filter, service, sort)| list_inquiries(page, filter, service, sort),
+    );
+
+    view! {
+        <Suspense fallback=|| view! { <p>"Laddar..."</p> }>
+            {move || Suspend::new(async move {
+                match inquiries.await {
+                    Ok(inquiry_page) => view! { <InquiryTable inquiry_page/> }.into_any(),
+                    Err(e) => admin_error_view(e),
+                }
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+fn InquiryTable(inquiry_page: InquiryPage) -> impl IntoView {
+    let InquiryPage { inquiries, filter, service, sort, page, total_pages, total } = inquiry_page;
+    let href = move |filter: &str, service: &str, sort: &str, page: i64| {
+        format!("/admin?status={}&service={}&sort={}&page={}", filter, service, sort, page)
+    };
+    let page_href = {
+        let (filter, service, sort) = (filter.clone(), service.clone(), sort.clone());
+        move |page: i64| href(&filter, &service, &sort, page)
+    };
+    let services = std::iter::once(("", "Alla tjänster"))
+        .chain(ServiceType::ALL.iter().map(|service| (service.as_str(), service.label())));
+    let filters = std::iter::once(("open", "Öppna"))
+        .chain(InquiryStatus::ALL.iter().map(|status| (status.as_str(), status.label())))
+        .chain(std::iter::once(("all", "Alla")));
+
+    view! {
+        <div class="admin-filters">
+            {filters.map(|(value, label)| view! {
+                <a href=href(value, &service, &sort, 1) class:active={filter == value}>{label}</a>
+            }).collect_view()}
+        </div>
+        <div class="admin-filters">
+            {services.map(|(value, label)| view! {
+                <a href=href(&filter, value, &sort, 1) class:active={service == value}>{label}</a>
+            }).collect_view()}
+            <a href=href(&filter, &service, "", 1) class:active={sort != "deadline"}>"Senast mottagna"</a>
+            <a href=href(&filter, &service, "deadline", 1) class:active={sort == "deadline"}>"Snarast önskat klart"</a>
+        </div>
+        <p class="admin-summary">{format!("{} ärenden", total)}</p>
+        <Show when=move || total == 0>
+            <p>"Inga ärenden ännu."</p>
+        </Show>
+        <table class="admin-table">
+            <thead>
+                <tr>
+                    <th>"#"</th>
+                    <th>"Mottaget"</th>
+                    <th>"Från"</th>
+                    <th>"Tjänst"</th>
+                    <th>"Meddelande"</th>
+                    <th>"Önskat klart"</th>
+                    <th>"Status"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {inquiries.into_iter().map(|inquiry| {
+                    let href = format!("/admin/inquiries/{}", inquiry.id);
+                    let preview: String = inquiry.message.chars().take(80).collect();
+                    view! {
+                        <tr>
+                            <td><a href=href.clone()>{inquiry.id}</a></td>
+                            <td>{inquiry.creation_date}</td>
+                            <td>{inquiry.email}</td>
+                            <td>{inquiry.service_type.map(|s| s.label())}</td>
+                            <td><a href=href>{preview}</a></td>
+                            <td>{inquiry.deadline}</td>
+                            <td>{inquiry.status.label()}</td>
+                        </tr>
+                    }
+                }).collect_view()}
+            </tbody>
+        </table>
+        <div class="admin-pagination">
+            {(page > 1).then(|| view! { <a href=page_href(page - 1)>"« Föregående"</a> })}
+            <span>{format!("Sida {} av {}", page, total_pages.max(1))}</span>
+            {(page < total_pages).then(|| view! { <a href=page_href(page + 1)>"Nästa »"</a> })}
+        </div>
+    }
+}
+
+#[component]
+pub fn AdminInquiryPage() -> impl IntoView {
+    let params = use_params_map();
+    let query_map = use_query_map();
+    let id = params.with(|params| params.get("id").and_then(|id| id.parse::<i64>().ok()));
+    let error = query_map.with(|params| {
+        params.get("error").and_then(|error_str| AdminError::from_str(&error_str).ok())
+    });
+
+    let inquiry = Resource::new(move || id, |id| async move {
+        match id {
+            Some(id) => get_inquiry(id).await,
+            None => Err(AdminError::NotFound),
+        }
+    });
+    let status_changes = Resource::new(move || id, |id| list_status_changes(id.unwrap_or_default()));
+    let attachments = Resource::new(move || id, |id| list_attachments(id.unwrap_or_default()));
+    let consent = Resource::new(move || id, |id| get_consent(id.unwrap_or_default()));
+    let messages = Resource::new(move || id, |id| list_messages(id.unwrap_or_default()));
+
+    view! {
+        {error.map(|e| view! { <p class="error-message">{e.get_user_message()}</p> })}
+        <Suspense fallback=|| view! { <p>"Laddar..."</p> }>
+            {move || Suspend::new(async move {
+                match (inquiry.await, status_changes.await, attachments.await, consent.await, messages.await) {
+                    (Ok(inquiry), Ok(status_changes), Ok(attachments), Ok(consent), Ok(messages)) => {
+                        view! { <InquiryDetails inquiry status_changes attachments consent messages/> }.into_any()
+                    }
+                    (Err(e), _, _, _, _)
+                    | (_, Err(e), _, _, _)
+                    | (_, _, Err(e), _, _)
+                    | (_, _, _, Err(e), _)
+                    | (_, _, _, _, Err(e)) => admin_error_view(e),
+                }
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+fn InquiryDetails(
+    inquiry: Inquiry,
+    status_changes: Vec<StatusChange>,
+    attachments: Vec<AttachmentInfo>,
+    consent: Option<Consent>,
+    messages: Vec<ThreadMessage>,
+) -> impl IntoView {
+    let delete_action = ServerAction::<DeleteInquiry>::new();
+    let status_action = ServerAction::<SetInquiryStatus>::new();
+    let reply_action = ServerAction::<ReplyToInquiry>::new();
+    // Nobody to reply to once the address has been removed
+    let can_reply = inquiry.anonymised_at.is_none();
+
+    view! {
+        <div class="admin-inquiry">
+            <a href="/admin">"« Tillbaka till inkorgen"</a>
+            <h2>{format!("Ärende #{}", inquiry.id)}</h2>
+            <dl>
+                <dt>"Mottaget"</dt>
+                <dd>{inquiry.creation_date.clone()}</dd>
+                <dt>"Från"</dt>
+                <dd><a href=format!("mailto:{}", inquiry.email)>{inquiry.email.clone()}</a></dd>
+                <dt>"Status"</dt>
+                <dd>{inquiry.status.label()}</dd>
+                {inquiry.service_type.map(|service_type| view! {
+                    <dt>"Tjänst"</dt>
+                    <dd>{service_type.label()}</dd>
+                })}
+                {inquiry.company.clone().map(|company| view! {
+                    <dt>"Företag"</dt>
+                    <dd>{company}</dd>
+                })}
+                {inquiry.budget.map(|budget| view! {
+                    <dt>"Budget"</dt>
+                    <dd>{budget.label()}</dd>
+                })}
+                {inquiry.deadline.clone().map(|deadline| view! {
+                    <dt>"Önskat klart"</dt>
+                    <dd>{deadline}</dd>
+                })}
+                {inquiry.finished_at.clone().map(|finished_at| view! {
+                    <dt>"Avslutat"</dt>
+                    <dd>{finished_at}</dd>
+                })}
+                {inquiry.anonymised_at.clone().map(|anonymised_at| view! {
+                    <dt>"Anonymiserat"</dt>
+                    <dd>{anonymised_at}</dd>
+                })}
+            </dl>
+            <h3>"Konversation"</h3>
+            <ol class="admin-thread">
+                <li class="incoming">
+                    <p class="admin-thread-meta">{format!("{} – {}", inquiry.creation_date, MessageDirection::Incoming.label())}</p>
+                    <pre class="admin-message">{inquiry.message}</pre>
+                </li>
+                {messages.into_iter().map(|message| view! {
+                    <li class=message.direction.as_str()>
+                        <p class="admin-thread-meta">
+                            {format!("{} – {}", message.created_at, message.direction.label())}
+                            {message.author.map(|author| format!(" ({})", author))}
+                        </p>
+                        {message.delivery_status.map(|status| view! {
+                            <p class=format!("admin-delivery {}", status.as_str())>
+                                {status.label()}
+                                {message.delivery_error.map(|error| format!(": {}", error))}
+                            </p>
+                        })}
+                        <pre class="admin-message">{message.body}</pre>
+                    </li>
+                }).collect_view()}
+            </ol>
+            {can_reply.then(|| view! {
+                <ActionForm action=reply_action attr:class="admin-reply">
+                    <input type="hidden" name="id" value=inquiry.id/>
+                    <label for="reply">"Svara kunden"</label>
+                    <textarea id="reply" name="body" rows="6" required></textarea>
+                    <input type="submit" value="Skicka svar"/>
+                </ActionForm>
+            })}
+            {(!attachments.is_empty()).then(|| view! { <h3>"Bilagor"</h3> })}
+            <ul class="admin-attachments">
+                {attachments.into_iter().map(|attachment| view! {
+                    <li>
+                        <a href=format!("/admin/attachments/{}", attachment.id)>{attachment.filename}</a>
+                        {format!(" ({}, {} kB)", attachment.content_type, (attachment.size + 1023) / 1024)}
+                    </li>
+                }).collect_view()}
+            </ul>
+            <h3>"Samtycke"</h3>
+            {match consent {
+                Some(consent) => view! {
+                    <p>{format!("Godkände villkoren version {} den {}:", consent.version, consent.consented_at)}</p>
+                    <blockquote class="admin-consent">{consent.text}</blockquote>
+                    {consent.ip_hash.map(|ip_hash| view! { <p class="admin-consent-ip">{format!("IP-adress (hashad): {}", ip_hash)}</p> })}
+                }.into_any(),
+                // Inquiries from before consents were recorded
+                None => view! { <p>"Inget samtycke registrerat."</p> }.into_any(),
+            }}
+            <ActionForm action=status_action>
+                <input type="hidden" name="id" value=inquiry.id/>
+                <label for="status">"Flytta till: "</label>
+                <select id="status" name="status">
+                    {inquiry.status.next_states().iter().map(|status| view! {
+                        <option value=status.as_str()>{status.label()}</option>
+                    }).collect_view()}
+                </select>
+                <input type="submit" value="Ändra status"/>
+            </ActionForm>
+            <h3>"Historik"</h3>
+            <ul class="admin-history">
+                {status_changes.into_iter().map(|change| view! {
+                    <li>{format!("{}: {} → {} ({})", change.changed_at, change.from_status.label(), change.to_status.label(), change.actor)}</li>
+                }).collect_view()}
+                <li>{format!("{}: Mottaget", inquiry.creation_date)}</li>
+            </ul>
+            <ActionForm action=delete_action>
+                <input type="hidden" name="id" value=inquiry.id/>
+                <input type="submit" class="danger" value="Radera ärendet"/>
+            </ActionForm>
+        </div>
+    }
+}
+
+#[component]
+pub fn AdminBlocklistPage() -> impl IntoView {
+    let add_action = ServerAction::<AddBlockedDomain>::new();
+    let remove_action = ServerAction::<RemoveBlockedDomain>::new();
+    let query_map = use_query_map();
+    let error = query_map.with(|params| {
+        params.get("error").and_then(|error_str| AdminError::from_str(&error_str).ok())
+    });
+
+    let domains = Resource::new(|| (), |_| list_blocked_domains());
+
+    view! {
+        <div class="admin-blocklist">
+            <h2>"Blockerade domäner"</h2>
+            <p>"Adresser på de här domänerna och deras underdomäner kan inte skicka meddelanden. Utöver listan nedan blockeras kända engångsadresser."</p>
+            {error.map(|e| view! { <p class="error-message">{e.get_user_message()}</p> })}
+            <ActionForm action=add_action>
+                <div class="form-field">
+                    <label for="domain">"Domän:"</label>
+                    <input id="domain" type="text" name="domain" placeholder="example.com" required/>
+                </div>
+                <div class="form-field">
+                    <label for="reason">"Anledning:"</label>
+                    <input id="reason" type="text" name="reason"/>
+                </div>
+                <input type="submit" value="Blockera"/>
+            </ActionForm>
+            <Suspense fallback=|| view! { <p>"Laddar..."</p> }>
+                {move || Suspend::new(async move {
+                    match domains.await {
+                        Ok(domains) => view! {
+                            <table class="admin-table">
+                                <thead>
+                                    <tr>
+                                        <th>"Domän"</th>
+                                        <th>"Anledning"</th>
+                                        <th>"Tillagd"</th>
+                                        <th></th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    {domains.into_iter().map(|blocked| view! {
+                                        <tr>
+                                            <td>{blocked.domain.clone()}</td>
+                                            <td>{blocked.reason.unwrap_or_default()}</td>
+                                            <td>{format!("{} ({})", blocked.created_at, blocked.added_by)}</td>
+                                            <td>
+                                                <ActionForm action=remove_action>
+                                                    <input type="hidden" name="domain" value=blocked.domain/>
+                                                    <input type="submit" class="danger" value="Ta bort"/>
+                                                </ActionForm>
+                                            </td>
+                                        </tr>
+                                    }).collect_view()}
+                                </tbody>
+                            </table>
+                        }.into_any(),
+                        Err(e) => admin_error_view(e),
+                    }
+                })}
+            </Suspense>
+        </div>
+    }
+}
+
+#[component]
+pub fn AdminBookingsPage() -> impl IntoView {
+    let cancel_action = ServerAction::<AdminCancelBooking>::new();
+    let add_action = ServerAction::<AddClosedDate>::new();
+    let remove_action = ServerAction::<RemoveClosedDate>::new();
+    let query_map = use_query_map();
+    let error = query_map.with(|params| {
+        params.get("error").and_then(|error_str| AdminError::from_str(&error_str).ok())
+    });
+
+    let bookings = Resource::new(|| (), |_| list_bookings());
+    let closed_dates = Resource::new(|| (), |_| list_closed_dates());
+
+    view! {
+        <div class="admin-bookings">
+            <h2>"Kommande bokningar"</h2>
+            {error.map(|e| view! { <p class="error-message">{e.get_user_message()}</p> })}
+            <Suspense fallback=|| view! { <p>"Laddar..."</p> }>
+                {move || Suspend::new(async move {
+                    match bookings.await {
+                        Ok(bookings) => view! {
+                            <table class="admin-table">
+                                <thead>
+                                    <tr>
+                                        <th>"Tid"</th>
+                                        <th>"Från"</th>
+                                        <th>"Ärende"</th>
+                                        <th>"Status"</th>
+                                        <th></th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    {bookings.into_iter().map(|booking| view! {
+                                        <tr>
+                                            <td>{slot_label(booking.slot_start)}</td>
+                                            <td><a href=format!("mailto:{}", booking.email)>{booking.email.clone()}</a></td>
+                                            <td>{booking.description}</td>
+                                            <td>{booking.status.label()}</td>
+                                            <td>
+                                                {(booking.status != BookingStatus::Cancelled).then(|| view! {
+                                                    <ActionForm action=cancel_action>
+                                                        <input type="hidden" name="id" value=booking.id/>
+                                                        <input type="submit" class="danger" value="Avboka"/>
+                                                    </ActionForm>
+                                                })}
+                                            </td>
+                                        </tr>
+                                    }).collect_view()}
+                                </tbody>
+                            </table>
+                        }.into_any(),
+                        Err(e) => admin_error_view(e),
+                    }
+                })}
+            </Suspense>
+
+            <h2>"Stängda dagar"</h2>
+            <p>"Inga tider kan bokas de här dagarna. Öppettiderna per veckodag finns i tabellen booking_hours."</p>
+            <ActionForm action=add_action>
+                <div class="form-field">
+                    <label for="date">"Datum:"</label>
+                    <input id="date" type="date" name="date" required/>
+                </div>
+                <div class="form-field">
+                    <label for="reason">"Anledning:"</label>
+                    <input id="reason" type="text" name="reason"/>
+                </div>
+                <input type="submit" value="Stäng dagen"/>
+            </ActionForm>
+            <Suspense fallback=|| view! { <p>"Laddar..."</p> }>
+                {move || Suspend::new(async move {
+                    match closed_dates.await {
+                        Ok(closed_dates) => view! {
+                            <table class="admin-table">
+                                <thead>
+                                    <tr>
+                                        <th>"Datum"</th>
+                                        <th>"Anledning"</th>
+                                        <th>"Tillagd"</th>
+                                        <th></th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    {closed_dates.into_iter().map(|closed| view! {
+                                        <tr>
+                                            <td>{closed.date.clone()}</td>
+                                            <td>{closed.reason.unwrap_or_default()}</td>
+                                            <td>{format!("{} ({})", closed.created_at, closed.added_by)}</td>
+                                            <td>
+                                                <ActionForm action=remove_action>
+                                                    <input type="hidden" name="date" value=closed.date/>
+                                                    <input type="submit" class="danger" value="Ta bort"/>
+                                                </ActionForm>
+                                            </td>
+                                        </tr>
+                                    }).collect_view()}
+                                </tbody>
+                            </table>
+                        }.into_any(),
+                        Err(e) => admin_error_view(e),
+                    }
+                })}
+            </Suspense>
+        </div>
+    }
+}
+
+#[server(AdminLogin, "/api")]
+pub async fn admin_login(username: String, password: String) -> Result<(), AdminError> {
+    use self::ssr::{hash_token, new_token, set_cookie, verify_credentials, SESSION_COOKIE, SESSION_HOURS};
+    use crate::app::ssr::db;
+    use leptos_axum::redirect;
+    use leptos::logging::log;
+
+    if !crate::rate_limit::check_admin_login().await {
+        log!("Admin login throttled for user: {}", username);
+        let error = AdminError::RateLimited;
+        redirect(&format!("/admin/login?error={}", error));
+        return Err(error);
+    }
+
+    if !verify_credentials(username.trim(), &password) {
+        log!("Admin login failed for user: {}", username);
+        let error = AdminError::InvalidCredentials;
+        redirect(&format!("/admin/login?error={}", error));
+        return Err(error);
+    }
+
+    let token = new_token();
+    let mut conn = db().await?;
+
+    // Housekeeping, expired sessions are useless
+    sqlx::query("DELETE FROM admin_sessions WHERE expires_at <= CURRENT_TIMESTAMP")
+        .execute(&mut conn)
+        .await?;
+
+    sqlx::query("INSERT INTO admin_sessions (token_hash, username, expires_at) VALUES ($1, $2, datetime('now', $3))")
+        .bind(hash_token(&token))
+        .bind(username.trim())
+        .bind(format!("+{} hours", SESSION_HOURS))
+        .execute(&mut conn)
+        .await?;
+
+    log!("Admin {} logged in.", username.trim());
+    set_cookie(SESSION_COOKIE, &token, SESSION_HOURS * 3600);
+    redirect("/admin");
+    Ok(())
+}
+
+#[server(AdminLogout, "/api")]
+pub async fn admin_logout() -> Result<(), AdminError> {
+    use self::ssr::{cookie_value, hash_token, set_cookie, SESSION_COOKIE};
+    use crate::app::ssr::db;
+    use axum::http::HeaderMap;
+    use leptos_axum::{extract, redirect};
+
+    let headers: HeaderMap = extract().await.map_err(ServerFnError::from)?;
+    if let Some(token) = cookie_value(&headers, SESSION_COOKIE) {
+        let mut conn = db().await?;
+        sqlx::query("DELETE FROM admin_sessions WHERE token_hash = $1")
+            .bind(hash_token(&token))
+            .execute(&mut conn)
+            .await?;
+    }
+
+    set_cookie(SESSION_COOKIE, "", 0);
+    redirect("/admin/login");
+    Ok(())
+}
+
+/// Lists inquiries, newest first or by deadline. `filter` is `open`, `all` or one of the statuses,
+/// `service` a service type or empty.
+#[server(ListInquiries, "/api")]
+pub async fn list_inquiries(page: i64, filter: String, service: String, sort: String) -> Result<InquiryPage, AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+
+    require_admin().await?;
+    let mut conn = db().await?;
+
+    // Unknown filters show everything
+    let filter = if filter == "open" || InquiryStatus::from_str(&filter).is_ok() { filter } else { "all".to_string() };
+    let service = if ServiceType::from_str(&service).is_ok() { service } else { String::new() };
+    const CONDITION: &str = "($1 = 'all' OR ($1 = 'open' AND status != 'closed') OR status = $1) AND ($2 = '' OR service_type = $2)";
+    let (sort, order) = if sort == "deadline" {
+        ("deadline".to_string(), "deadline IS NULL, deadline, creation_date DESC, id DESC")
+    } else {
+        (String::new(), "creation_date DESC, id DESC")
+    };
+
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM emails WHERE {}", CONDITION))
+        .bind(&filter)
+        .bind(&service)
+        .fetch_one(&mut conn)
+        .await?;
+    let total_pages = (total + INQUIRIES_PER_PAGE - 1) / INQUIRIES_PER_PAGE;
+    let page = page.clamp(1, total_pages.max(1));
+
+    let inquiries: Vec<Inquiry> = sqlx::query_as::<_, Inquiry>(&format!(
+        "SELECT {} FROM emails WHERE {} ORDER BY {} LIMIT $3 OFFSET $4",
+        INQUIRY_COLUMNS, CONDITION, order
+    ))
+    .bind(&filter)
+    .bind(&service)
+    .bind(INQUIRIES_PER_PAGE)
+    .bind((page - 1) * INQUIRIES_PER_PAGE)
+    .fetch_all(&mut conn)
+    .await?
+    .into_iter()
+    .map(Inquiry::decrypted)
+    .collect();
+
+    Ok(InquiryPage { inquiries, filter, service, sort, page, total_pages, total })
+}
+
+#[server(GetInquiry, "/api")]
+pub async fn get_inquiry(id: i64) -> Result<Inquiry, AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+
+    require_admin().await?;
+    let mut conn = db().await?;
+
+    sqlx::query_as::<_, Inquiry>(&format!("SELECT {} FROM emails WHERE id = $1", INQUIRY_COLUMNS))
+        .bind(id)
+        .fetch_optional(&mut conn)
+        .await?
+        .map(Inquiry::decrypted)
+        .ok_or(AdminError::NotFound)
+}
+
+#[server(DeleteInquiry, "/api")]
+pub async fn delete_inquiry(id: i64) -> Result<(), AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+    use leptos_axum::redirect;
+    use leptos::logging::log;
+    use sqlx::Connection;
+
+    let username = require_admin().await?;
+    let mut conn = db().await?;
+
+    let mut tx = conn.begin().await?;
+    for query in [
+        "DELETE FROM status_changes WHERE email_id = $1",
+        "DELETE FROM sent_receipts WHERE email_id = $1",
+        "DELETE FROM attachments WHERE email_id = $1",
+        "DELETE FROM consents WHERE email_id = $1",
+        "DELETE FROM webhook_attempts WHERE email_id = $1",
+        "DELETE FROM jobs WHERE email_id = $1",
+        "DELETE FROM dead_jobs WHERE email_id = $1",
+        "DELETE FROM messages WHERE email_id = $1",
+    ] {
+        sqlx::query(query).bind(id).execute(&mut *tx).await?;
+    }
+    let result = sqlx::query("DELETE FROM emails WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AdminError::NotFound);
+    }
+    tx.commit().await?;
+
+    log!("Inquiry {} deleted by {}.", id, username);
+    redirect("/admin");
+    Ok(())
+}
+
+#[server(ListStatusChanges, "/api")]
+pub async fn list_status_changes(id: i64) -> Result<Vec<StatusChange>, AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+
+    require_admin().await?;
+    let mut conn = db().await?;
+
+    Ok(sqlx::query_as::<_, StatusChange>(
+        "SELECT from_status, to_status, actor, changed_at FROM status_changes WHERE email_id = $1 ORDER BY changed_at DESC, id DESC",
+    )
+    .bind(id)
+    .fetch_all(&mut conn)
+    .await?)
+}
+
+/// Moves an inquiry to a new status and records who did it. Closing an inquiry starts the
+/// retention grace period, reopening it stops it.
+#[server(SetInquiryStatus, "/api")]
+pub async fn set_inquiry_status(id: i64, status: InquiryStatus) -> Result<(), AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+    use leptos_axum::redirect;
+    use leptos::logging::log;
+    use sqlx::Connection;
+
+    let username = require_admin().await?;
+    let mut conn = db().await?;
+    let mut tx = conn.begin().await?;
+
+    let current: InquiryStatus = sqlx::query_scalar("SELECT status FROM emails WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AdminError::NotFound)?;
+
+    if !current.can_transition_to(status) {
+        let error = AdminError::InvalidStatusTransition;
+        redirect(&format!("/admin/inquiries/{}?error={}", id, error));
+        return Err(error);
+    }
+
+    let query = if status == InquiryStatus::Closed {
+        "UPDATE emails SET status = $1, finished_at = CURRENT_TIMESTAMP WHERE id = $2"
+    } else {
+        "UPDATE emails SET status = $1, finished_at = NULL WHERE id = $2"
+    };
+    sqlx::query(query).bind(status).bind(id).execute(&mut *tx).await?;
+
+    sqlx::query("INSERT INTO status_changes (email_id, from_status, to_status, actor) VALUES ($1, $2, $3, $4)")
+        .bind(id)
+        .bind(current)
+        .bind(status)
+        .bind(&username)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    log!("Inquiry {} moved from {} to {} by {}.", id, current, status, username);
+    redirect(&format!("/admin/inquiries/{}", id));
+    Ok(())
+}
+
+/// The conversation after the inquiry itself, oldest first.
+#[server(ListMessages, "/api")]
+pub async fn list_messages(id: i64) -> Result<Vec<ThreadMessage>, AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+    use crate::crypto::{decrypt_or_placeholder, Field};
+
+    require_admin().await?;
+    let mut conn = db().await?;
+
+    Ok(sqlx::query_as::<_, ThreadMessage>(
+        "SELECT id, direction, body, author, created_at, delivery_status, delivery_error FROM messages \
+         WHERE email_id = $1 ORDER BY created_at, id",
+    )
+    .bind(id)
+    .fetch_all(&mut conn)
+    .await?
+    .into_iter()
+    .map(|message| ThreadMessage { body: decrypt_or_placeholder(Field::MessageBody, &message.body), ..message })
+    .collect())
+}
+
+/// Mails a reply to the person who sent the inquiry and adds it to the conversation. The mail is
+/// queued together with the message, and the message shows whether it has been sent yet or
+/// failed for good.
+#[server(ReplyToInquiry, "/api")]
+pub async fn reply_to_inquiry(id: i64, body: String) -> Result<(), AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+    use crate::crypto::{decrypt, encrypt, Field};
+    use crate::inquiry::DeliveryStatus;
+    use leptos_axum::redirect;
+    use leptos::logging::log;
+    use sqlx::Connection;
+
+    let username = require_admin().await?;
+
+    let body = body.trim();
+    let error = if body.is_empty() {
+        Some(AdminError::EmptyReply)
+    } else if crate::validation::message_too_large(body) {
+        Some(AdminError::ReplyTooLarge)
+    } else if crate::validation::message_too_long(body) {
+        Some(AdminError::ReplyTooLong)
+    } else if !crate::mail::is_enabled() {
+        Some(AdminError::MailDisabled)
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        redirect(&format!("/admin/inquiries/{}?error={}", id, error));
+        return Err(error);
+    }
+
+    let mut conn = db().await?;
+    let mut tx = conn.begin().await?;
+
+    let email: Option<String> = sqlx::query_scalar("SELECT email FROM emails WHERE id = $1 AND anonymised_at IS NULL")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let Some(email) = email else {
+        let error = AdminError::NotFound;
+        redirect(&format!("/admin/inquiries/{}?error={}", id, error));
+        return Err(error);
+    };
+    let email = decrypt(Field::Email, &email).map_err(|e| AdminError::DatabaseError(e.to_string()))?;
+
+    let job_id = crate::mail::send_reply(&mut *tx, id, &email, body).await?;
+    sqlx::query(
+        "INSERT INTO messages (email_id, direction, body, author, delivery_status, job_id) VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(id)
+    .bind(MessageDirection::Outgoing)
+    .bind(encrypt(Field::MessageBody, body))
+    .bind(&username)
+    .bind(DeliveryStatus::Queued)
+    .bind(job_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    crate::jobs::wake();
+
+    log!("Reply to inquiry {} queued by {}.", id, username);
+    redirect(&format!("/admin/inquiries/{}", id));
+    Ok(())
+}
+
+#[server(ListAttachments, "/api")]
+pub async fn list_attachments(id: i64) -> Result<Vec<AttachmentInfo>, AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+
+    require_admin().await?;
+    let mut conn = db().await?;
+
+    Ok(sqlx::query_as::<_, AttachmentInfo>(
+        "SELECT id, filename, content_type, size FROM attachments WHERE email_id = $1 ORDER BY id",
+    )
+    .bind(id)
+    .fetch_all(&mut conn)
+    .await?)
+}
+
+/// The terms the person agreed to when sending the inquiry.
+#[server(GetConsent, "/api")]
+pub async fn get_consent(id: i64) -> Result<Option<Consent>, AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+
+    require_admin().await?;
+    let mut conn = db().await?;
+
+    Ok(crate::terms::ssr::for_inquiry(&mut conn, id).await?)
+}
+
+#[server(ListBlockedDomains, "/api")]
+pub async fn list_blocked_domains() -> Result<Vec<BlockedDomain>, AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+
+    require_admin().await?;
+    let mut conn = db().await?;
+
+    Ok(sqlx::query_as::<_, BlockedDomain>(
+        "SELECT domain, reason, added_by, created_at FROM blocked_domains ORDER BY domain",
+    )
+    .fetch_all(&mut conn)
+    .await?)
+}
+
+/// Blocks a domain (and its subdomains) from the contact form. Takes effect immediately.
+#[server(AddBlockedDomain, "/api")]
+pub async fn add_blocked_domain(domain: String, reason: Option<String>) -> Result<(), AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+    use crate::validation::normalise_domain;
+    use leptos_axum::redirect;
+    use leptos::logging::log;
+
+    let username = require_admin().await?;
+
+    // Accept pasted addresses as well as domains
+    let domain = domain.trim();
+    let domain = domain.rsplit_once('@').map_or(domain, |(_, domain)| domain);
+    let Some(domain) = normalise_domain(domain) else {
+        let error = AdminError::InvalidDomain;
+        redirect(&format!("/admin/blocklist?error={}", error));
+        return Err(error);
+    };
+    let reason = reason.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+
+    let mut conn = db().await?;
+    sqlx::query("INSERT OR IGNORE INTO blocked_domains (domain, reason, added_by) VALUES ($1, $2, $3)")
+        .bind(&domain)
+        .bind(reason)
+        .bind(&username)
+        .execute(&mut conn)
+        .await?;
+    crate::blocklist::reload().await?;
+
+    log!("Domain {} blocked by {}.", domain, username);
+    redirect("/admin/blocklist");
+    Ok(())
+}
+
+#[server(RemoveBlockedDomain, "/api")]
+pub async fn remove_blocked_domain(domain: String) -> Result<(), AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+    use leptos_axum::redirect;
+    use leptos::logging::log;
+
+    let username = require_admin().await?;
+    let mut conn = db().await?;
+
+    sqlx::query("DELETE FROM blocked_domains WHERE domain = $1")
+        .bind(&domain)
+        .execute(&mut conn)
+        .await?;
+    crate::blocklist::reload().await?;
+
+    log!("Domain {} unblocked by {}.", domain, username);
+    redirect("/admin/blocklist");
+    Ok(())
+}
+
+/// Bookings from today on, cancelled ones included so that a cancellation can be seen.
+#[server(ListBookings, "/api")]
+pub async fn list_bookings() -> Result<Vec<Booking>, AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+
+    require_admin().await?;
+    let mut conn = db().await?;
+
+    Ok(sqlx::query_as::<_, Booking>(
+        "SELECT id, slot_start, email, description, status, created_at, confirmed_at, cancelled_at FROM bookings \
+         WHERE slot_start >= date('now', 'localtime') ORDER BY slot_start, id",
+    )
+    .fetch_all(&mut conn)
+    .await?)
+}
+
+#[server(AdminCancelBooking, "/api")]
+pub async fn admin_cancel_booking(id: i64) -> Result<(), AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+    use leptos_axum::redirect;
+    use leptos::logging::log;
+    use sqlx::Connection;
+
+    let username = require_admin().await?;
+    let mut conn = db().await?;
+    let mut tx = conn.begin().await?;
+
+    let cancelled = sqlx::query_as::<_, (chrono::NaiveDateTime, String, bool)>(
+        "UPDATE bookings SET status = 'cancelled', cancelled_at = CURRENT_TIMESTAMP WHERE id = $1 AND status != 'cancelled' \
+         RETURNING slot_start, email, confirmed_at IS NOT NULL",
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some((slot, email, was_confirmed)) = cancelled else {
+        return Err(AdminError::NotFound);
+    };
+
+    // The customer is expecting us, an unconfirmed booking was never announced
+    if was_confirmed && slot > crate::booking::ssr::now() {
+        crate::mail::send_booking_cancelled(&mut tx, &email, &slot_label(slot)).await?;
+    }
+    tx.commit().await?;
+    crate::jobs::wake();
+
+    log!("Booking {} cancelled by {}.", id, username);
+    redirect("/admin/bookings");
+    Ok(())
+}
+
+#[server(ListClosedDates, "/api")]
+pub async fn list_closed_dates() -> Result<Vec<ClosedDate>, AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+
+    require_admin().await?;
+    let mut conn = db().await?;
+
+    Ok(sqlx::query_as::<_, ClosedDate>(
+        "SELECT date, reason, added_by, created_at FROM booking_closed_dates \
+         WHERE date >= date('now', 'localtime') ORDER BY date",
+    )
+    .fetch_all(&mut conn)
+    .await?)
+}
+
+/// Closes a day for bookings. Refused while the day has bookings, the customers would otherwise
+/// turn up on a closed day.
+#[server(AddClosedDate, "/api")]
+pub async fn add_closed_date(date: String, reason: Option<String>) -> Result<(), AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+    use leptos_axum::redirect;
+    use leptos::logging::log;
+
+    let username = require_admin().await?;
+
+    let Ok(date) = chrono::NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d") else {
+        let error = AdminError::InvalidDate;
+        redirect(&format!("/admin/bookings?error={}", error));
+        return Err(error);
+    };
+    let reason = reason.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+
+    let mut conn = db().await?;
+    let booked: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM bookings WHERE date(slot_start) = $1 AND status != 'cancelled'")
+        .bind(date)
+        .fetch_one(&mut conn)
+        .await?;
+    if booked > 0 {
+        log!("Not closing {}, it has {} bookings.", date, booked);
+        let error = AdminError::DateHasBookings;
+        redirect(&format!("/admin/bookings?error={}", error));
+        return Err(error);
+    }
+
+    sqlx::query("INSERT OR IGNORE INTO booking_closed_dates (date, reason, added_by) VALUES ($1, $2, $3)")
+        .bind(date)
+        .bind(reason)
+        .bind(&username)
+        .execute(&mut conn)
+        .await?;
+
+    log!("Bookings closed on {} by {}.", date, username);
+    redirect("/admin/bookings");
+    Ok(())
+}
+
+#[server(RemoveClosedDate, "/api")]
+pub async fn remove_closed_date(date: String) -> Result<(), AdminError> {
+    use self::ssr::require_admin;
+    use crate::app::ssr::db;
+    use leptos_axum::redirect;
+    use leptos::logging::log;
+
+    let username = require_admin().await?;
+    let mut conn = db().await?;
+
+    sqlx::query("DELETE FROM booking_closed_dates WHERE date = $1")
+        .bind(&date)
+        .execute(&mut conn)
+        .await?;
+
+    log!("Bookings reopened on {} by {}.", date, username);
+    redirect("/admin/bookings");
+    Ok(())
+}
@@ -1,11 +1,14 @@
 use leptos::prelude::*;
 use leptos_meta::{provide_meta_context, MetaTags, Stylesheet, Title};
 use leptos_router::{
-    components::{Route, Router, Routes},
-    path,
+    components::{ParentRoute, Route, Router, Routes},
+    path, SsrMode,
 };
 
+use crate::admin::{AdminBlocklistPage, AdminBookingsPage, AdminInboxPage, AdminInquiryPage, AdminLayout, AdminLoginPage};
+use crate::booking::{BookingForm, BookingPage};
 use crate::contact::ContactForm;
+use crate::portal::{PortalLoginPage, PortalPage};
 
 #[cfg(feature="ssr")]
 pub mod ssr {
@@ -56,7 +59,19 @@ pub fn App() -> impl IntoView {
             <NavBar/>
             <main>
                 <Routes fallback=|| "Page not found.".into_view()>
-                    <Route path=path!("") view=HomePage/>
+                    // The booking form waits for the free slots, rendered in full so that it works without JS.
+                    <Route path=path!("") view=HomePage ssr=SsrMode::Async/>
+                    <Route path=path!("/booking/:id") view=BookingPage ssr=SsrMode::Async/>
+                    <Route path=path!("/portal") view=PortalPage ssr=SsrMode::Async/>
+                    <Route path=path!("/portal/login") view=PortalLoginPage/>
+                    // Admin pages are rendered fully before sending, so that they can redirect to the login page.
+                    <Route path=path!("/admin/login") view=AdminLoginPage/>
+                    <ParentRoute path=path!("/admin") view=AdminLayout ssr=SsrMode::Async>
+                        <Route path=path!("") view=AdminInboxPage ssr=SsrMode::Async/>
+                        <Route path=path!("inquiries/:id") view=AdminInquiryPage ssr=SsrMode::Async/>
+                        <Route path=path!("bookings") view=AdminBookingsPage ssr=SsrMode::Async/>
+                        <Route path=path!("blocklist") view=AdminBlocklistPage ssr=SsrMode::Async/>
+                    </ParentRoute>
                 </Routes>
             </main>
             <Footer/>
@@ -74,6 +89,7 @@ fn Footer() -> impl IntoView {
             // Wrap the text elements in a div for layout
             <div class="footer-text">
                 <div><a href="mailto:hej@tivanderit.se">"✉️ hej@tivanderit.se"</a></div>
+                <div><a href="/portal">"Mina ärenden"</a></div>
                 <div>"Copyright © 2025 - Tivander IT AB"</div>
             </div>
         </div>
@@ -107,6 +123,7 @@ fn HomePage() -> impl IntoView {
                 <div class="card-text">
                     <h3>"Hjälp med persondatorer"</h3>
                     <p>"Har du problem med datorn? Vi kan hjälpa dig att rensa upp persondatorn, fixa allt från små till större fel och få allt att fungera smidigt igen. Om din dator krånglar eller börjar kännas långsam, finns vi här för att hjälpa till."</p>
+                    <p><a href="#booking">"Boka en tid direkt »"</a></p>
                 </div>
             </div>
         </div>
@@ -118,6 +135,13 @@ fn HomePage() -> impl IntoView {
             </div>
             <ContactForm/>
         </div>
+        <div id="booking">
+            <div class="booking-info">
+                <h2>"Boka datorhjälp"</h2>
+                <p>"Välj en tid som passar dig och beskriv kort vad som krånglar. Du får ett mejl med en länk för att bekräfta bokningen, och en för att avboka om något kommer emellan."</p>
+            </div>
+            <BookingForm/>
+        </div>
     }
 }
 
@@ -133,6 +157,7 @@ fn NavBar() -> impl IntoView {
             <a href="#home">"Vårt uppdrag"</a>
             <a href="#about">"Vad vi erbjuder"</a>
             <a href="#contact">"Kontakta oss"</a>
+            <a href="#booking">"Boka tid"</a>
         </nav>
     }
 }
@@ -0,0 +1,185 @@
+//! Files attached to contact submissions (e.g. a screenshot of an error message) and to mail
+//! from customers that continues an inquiry.
+//!
+//! Attachments are stored as BLOBs in the `attachments` table, so that they are backed up, deleted
+//! and purged together with the inquiry they belong to. The type is decided from the file content,
+//! the name and type sent by the browser are not trusted.
+
+use crate::admin::ssr::{admin_for_token, cookie_value, SESSION_COOKIE};
+use crate::app::ssr::db;
+use crate::error::ContactFormError;
+use crate::validation::{ATTACHMENT_MAX_BYTES, MAX_ATTACHMENTS};
+use axum::{
+    extract::Path,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use leptos::server_fn::codec::MultipartData;
+use std::collections::HashMap;
+
+/// Text fields are short, this only guards against memory exhaustion.
+const TEXT_FIELD_MAX_BYTES: usize = 256 * 1024;
+
+/// Upper bound for a whole contact form request, enforced before the body is read.
+pub const REQUEST_MAX_BYTES: usize = MAX_ATTACHMENTS * ATTACHMENT_MAX_BYTES + 1024 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: &'static str,
+    pub data: Vec<u8>,
+}
+
+/// Recognises the allowed file types by their magic bytes.
+fn sniff_content_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if data.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else {
+        None
+    }
+}
+
+/// Keeps the file name displayable and safe to put in a `Content-Disposition` header.
+fn sanitise_filename(filename: &str) -> String {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .take(100)
+        .collect();
+    if name.trim().is_empty() { "bilaga".to_string() } else { name }
+}
+
+/// Reads a multipart contact form into its text fields and attachments, enforcing the size, count
+/// and type limits while streaming.
+pub async fn read_multipart(
+    data: MultipartData,
+) -> Result<(HashMap<String, String>, Vec<Attachment>), ContactFormError> {
+    let mut multipart = data.into_inner().ok_or(ContactFormError::AttachmentUploadFailed)?;
+    let mut fields = HashMap::new();
+    let mut attachments = Vec::new();
+
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| ContactFormError::AttachmentUploadFailed)?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        let filename = field.file_name().map(str::to_string);
+        let limit = if filename.is_some() { ATTACHMENT_MAX_BYTES } else { TEXT_FIELD_MAX_BYTES };
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.chunk().await.map_err(|_| ContactFormError::AttachmentUploadFailed)? {
+            if bytes.len() + chunk.len() > limit {
+                return Err(if filename.is_some() {
+                    ContactFormError::AttachmentTooLarge
+                } else {
+                    ContactFormError::AttachmentUploadFailed
+                });
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        match filename {
+            // Browsers send an empty part when no file was picked
+            Some(filename) if filename.is_empty() && bytes.is_empty() => {}
+            Some(filename) => {
+                if attachments.len() == MAX_ATTACHMENTS {
+                    return Err(ContactFormError::TooManyAttachments);
+                }
+                let content_type = sniff_content_type(&bytes).ok_or(ContactFormError::AttachmentTypeNotAllowed)?;
+                attachments.push(Attachment {
+                    filename: sanitise_filename(&filename),
+                    content_type,
+                    data: bytes,
+                });
+            }
+            None => {
+                let value = String::from_utf8(bytes).map_err(|_| ContactFormError::AttachmentUploadFailed)?;
+                fields.insert(name, value);
+            }
+        }
+    }
+
+    Ok((fields, attachments))
+}
+
+/// An attachment from an incoming mail, if it is of an allowed type and size. Other files are
+/// left out, the mail itself is still accepted.
+pub fn from_mail(filename: Option<&str>, data: &[u8]) -> Option<Attachment> {
+    if data.is_empty() || data.len() > ATTACHMENT_MAX_BYTES {
+        return None;
+    }
+    Some(Attachment {
+        filename: sanitise_filename(filename.unwrap_or_default()),
+        content_type: sniff_content_type(data)?,
+        data: data.to_vec(),
+    })
+}
+
+pub async fn store(
+    conn: &mut sqlx::SqliteConnection,
+    email_id: i64,
+    attachments: &[Attachment],
+) -> Result<(), sqlx::Error> {
+    for attachment in attachments {
+        sqlx::query("INSERT INTO attachments (email_id, filename, content_type, size, data) VALUES ($1, $2, $3, $4, $5)")
+            .bind(email_id)
+            .bind(&attachment.filename)
+            .bind(attachment.content_type)
+            .bind(attachment.data.len() as i64)
+            .bind(&attachment.data)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// `GET /admin/attachments/{id}`, only for logged in admins. Always served as a download, so an
+/// uploaded file is never rendered in the context of the site.
+pub async fn download(Path(id): Path<i64>, headers: HeaderMap) -> Response {
+    let Some(token) = cookie_value(&headers, SESSION_COOKIE) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if admin_for_token(&token).await.is_err() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let attachment: Result<Option<(String, String, Vec<u8>)>, _> = async {
+        let mut conn = db().await.map_err(|e| e.to_string())?;
+        sqlx::query_as("SELECT filename, content_type, data FROM attachments WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&mut conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+    .await;
+
+    match attachment {
+        Ok(Some((filename, content_type, data))) => {
+            let disposition = format!("attachment; filename=\"{}\"", filename.replace(|c: char| !c.is_ascii(), "_"));
+            (
+                [
+                    (header::CONTENT_TYPE, HeaderValue::from_str(&content_type).unwrap_or(HeaderValue::from_static("application/octet-stream"))),
+                    (header::CONTENT_DISPOSITION, HeaderValue::from_str(&disposition).unwrap_or(HeaderValue::from_static("attachment"))),
+                    (header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+                ],
+                data,
+            )
+                .into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            leptos::logging::log!("Could not load attachment {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Email domains that may not be used in the contact form.
+//!
+//! The list is the bundled `blocklists/disposable_domains.txt` plus the domains added in the
+//! admin interface (the `blocked_domains` table). It is kept in memory and reloaded whenever an
+//! admin changes it, so no restart is needed.
+
+use crate::app::ssr::db;
+use leptos::logging::log;
+use leptos::server_fn::ServerFnError;
+use std::collections::HashSet;
+use std::sync::RwLock;
+
+const BUNDLED: &str = include_str!("../blocklists/disposable_domains.txt");
+
+/// `None` until first used.
+static BLOCKED: RwLock<Option<HashSet<String>>> = RwLock::new(None);
+
+fn bundled_domains() -> impl Iterator<Item = String> {
+    BUNDLED
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+}
+
+/// Reads the list again and returns the number of blocked domains.
+pub async fn reload() -> Result<usize, ServerFnError> {
+    let mut conn = db().await?;
+    let managed: Vec<String> = sqlx::query_scalar("SELECT domain FROM blocked_domains")
+        .fetch_all(&mut conn)
+        .await?;
+
+    let domains: HashSet<String> = bundled_domains().chain(managed).collect();
+    let count = domains.len();
+    *BLOCKED.write().unwrap_or_else(|e| e.into_inner()) = Some(domains);
+
+    log!("Loaded {} blocked email domains.", count);
+    Ok(count)
+}
+
+/// True if `domain` (normalised, see `validation::normalise_domain`) or one of its parent domains
+/// is blocked.
+pub async fn is_blocked(domain: &str) -> Result<bool, ServerFnError> {
+    if BLOCKED.read().unwrap_or_else(|e| e.into_inner()).is_none() {
+        reload().await?;
+    }
+
+    let blocked = BLOCKED.read().unwrap_or_else(|e| e.into_inner());
+    let Some(blocked) = blocked.as_ref() else {
+        return Ok(false);
+    };
+
+    // "a.b.example.com" checks "a.b.example.com", "b.example.com" and "example.com"
+    let mut candidate = domain;
+    loop {
+        if blocked.contains(candidate) {
+            return Ok(true);
+        }
+        match candidate.split_once('.') {
+            Some((_, parent)) if parent.contains('.') => candidate = parent,
+            _ => return Ok(false),
+        }
+    }
+}
@@ -0,0 +1,879 @@
+//! Appointments with the PC technician ("Hjälp med persondatorer").
+//!
+//! Free slots are generated from the weekly opening hours in `booking_hours`, minus the dates in
+//! `booking_closed_dates` and the slots already held by a booking. A new booking is `pending` and
+//! holds its slot for `BOOKING_CONFIRM_MINUTES` while the customer follows the confirmation link
+//! in the mail; unconfirmed bookings are then released. Both the confirmation and the
+//! cancellation link are signed, so no account is needed.
+
+use leptos::{html, prelude::*};
+use leptos_router::hooks::{use_params_map, use_query_map};
+use crate::error::BookingError;
+use crate::terms::BOOKING_TERMS;
+use crate::validation::{validate_email, validate_message, validate_terms};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// The format of a slot in forms and links, e.g. `2026-10-20T09:00`.
+const SLOT_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "ssr", sqlx(rename_all = "snake_case"))]
+pub enum BookingStatus {
+    /// Waiting for the customer to follow the confirmation link.
+    Pending,
+    Confirmed,
+    Cancelled,
+}
+
+impl BookingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BookingStatus::Pending => "pending",
+            BookingStatus::Confirmed => "confirmed",
+            BookingStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BookingStatus::Pending => "Väntar på bekräftelse",
+            BookingStatus::Confirmed => "Bekräftad",
+            BookingStatus::Cancelled => "Avbokad",
+        }
+    }
+}
+
+impl fmt::Display for BookingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct Booking {
+    pub id: i64,
+    /// Local time.
+    pub slot_start: NaiveDateTime,
+    pub email: String,
+    pub description: String,
+    pub status: BookingStatus,
+    pub created_at: String,
+    pub confirmed_at: Option<String>,
+    pub cancelled_at: Option<String>,
+}
+
+/// The free slots of one day.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaySlots {
+    pub date: NaiveDate,
+    pub times: Vec<NaiveTime>,
+}
+
+pub fn slot_value(slot: NaiveDateTime) -> String {
+    slot.format(SLOT_FORMAT).to_string()
+}
+
+pub fn parse_slot(slot: &str) -> Result<NaiveDateTime, BookingError> {
+    match slot.trim() {
+        "" => Err(BookingError::MissingSlot),
+        s => NaiveDateTime::parse_from_str(s, SLOT_FORMAT).map_err(|_| BookingError::SlotUnavailable),
+    }
+}
+
+fn weekday_label(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "måndag",
+        Weekday::Tue => "tisdag",
+        Weekday::Wed => "onsdag",
+        Weekday::Thu => "torsdag",
+        Weekday::Fri => "fredag",
+        Weekday::Sat => "lördag",
+        Weekday::Sun => "söndag",
+    }
+}
+
+/// E.g. "tisdag 20 oktober".
+pub fn date_label(date: NaiveDate) -> String {
+    const MONTHS: [&str; 12] = [
+        "januari", "februari", "mars", "april", "maj", "juni",
+        "juli", "augusti", "september", "oktober", "november", "december",
+    ];
+    format!("{} {} {}", weekday_label(date.weekday()), date.day(), MONTHS[date.month0() as usize])
+}
+
+/// E.g. "tisdag 20 oktober kl. 09:00".
+pub fn slot_label(slot: NaiveDateTime) -> String {
+    format!("{} kl. {}", date_label(slot.date()), slot.format("%H:%M"))
+}
+
+/// Validates the booking form and returns the chosen slot. Whether the slot is actually free is
+/// only known to the server. Every invalid field is reported, like `validate_contact_form`.
+pub fn validate_booking(slot: &str, email: &str, description: &str, terms_accepted: bool) -> Result<NaiveDateTime, BookingError> {
+    let slot = parse_slot(slot);
+
+    let mut errors: Vec<BookingError> = [
+        slot.as_ref().err().cloned(),
+        validate_email(email).err().map(BookingError::from),
+        validate_message(description).err().map(BookingError::from),
+        validate_terms(terms_accepted).err().map(BookingError::from),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    match slot {
+        Ok(slot) if errors.is_empty() => Ok(slot),
+        _ if errors.len() == 1 => Err(errors.remove(0)),
+        _ => Err(BookingError::Fields(errors)),
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{slot_value, DaySlots};
+    use crate::signing;
+    use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+    use std::collections::HashSet;
+    use std::net::IpAddr;
+
+    const LINK_PURPOSE: &str = "booking-link";
+
+    #[derive(Clone, Debug)]
+    pub struct BookingConfig {
+        pub slot_minutes: i64,
+        pub days_ahead: i64,
+        pub min_notice_hours: i64,
+        pub confirm_minutes: i64,
+        pub max_pending: i64,
+        pub client_max_pending: i64,
+    }
+
+    impl BookingConfig {
+        /// Reads `BOOKING_SLOT_MINUTES` (default 60), `BOOKING_DAYS_AHEAD` (default 14),
+        /// `BOOKING_MIN_NOTICE_HOURS` (default 24), `BOOKING_CONFIRM_MINUTES` (default 60),
+        /// `BOOKING_MAX_PENDING` (default 2) and `BOOKING_CLIENT_MAX_PENDING` (default 4).
+        pub fn from_env() -> Self {
+            fn env_or(name: &str, default: i64) -> i64 {
+                std::env::var(name)
+                    .ok()
+                    .and_then(|v| v.parse::<i64>().ok())
+                    .filter(|v| *v > 0)
+                    .unwrap_or(default)
+            }
+
+            BookingConfig {
+                slot_minutes: env_or("BOOKING_SLOT_MINUTES", 60),
+                days_ahead: env_or("BOOKING_DAYS_AHEAD", 14),
+                min_notice_hours: env_or("BOOKING_MIN_NOTICE_HOURS", 24),
+                confirm_minutes: env_or("BOOKING_CONFIRM_MINUTES", 60),
+                max_pending: env_or("BOOKING_MAX_PENDING", 2),
+                client_max_pending: env_or("BOOKING_CLIENT_MAX_PENDING", 4),
+            }
+        }
+    }
+
+    /// A row in `booking_hours`.
+    #[derive(Clone, Debug, sqlx::FromRow)]
+    pub struct OpeningHours {
+        pub weekday: u32,
+        pub start_time: String,
+        pub end_time: String,
+    }
+
+    /// The slots that fit completely within the opening hours of `date`.
+    pub fn slot_starts(date: NaiveDate, hours: &[OpeningHours], slot_minutes: i64) -> Vec<NaiveDateTime> {
+        let slot_length = Duration::minutes(slot_minutes);
+        let mut starts: Vec<NaiveDateTime> = hours
+            .iter()
+            .filter(|hours| hours.weekday == date.weekday().number_from_monday())
+            .filter_map(|hours| {
+                let start = NaiveTime::parse_from_str(&hours.start_time, "%H:%M").ok()?;
+                let end = NaiveTime::parse_from_str(&hours.end_time, "%H:%M").ok()?;
+                Some((date.and_time(start), date.and_time(end)))
+            })
+            .flat_map(|(start, end)| {
+                std::iter::successors(Some(start), move |slot| Some(*slot + slot_length))
+                    .take_while(move |slot| *slot + slot_length <= end)
+            })
+            .collect();
+        starts.sort();
+        starts.dedup();
+        starts
+    }
+
+    /// Releases the slots of bookings that weren't confirmed in time.
+    pub async fn release_unconfirmed(conn: &mut sqlx::SqliteConnection, config: &BookingConfig) -> Result<u64, sqlx::Error> {
+        Ok(sqlx::query(
+            "UPDATE bookings SET status = 'cancelled', cancelled_at = CURRENT_TIMESTAMP \
+             WHERE status = 'pending' AND created_at <= datetime('now', $1)",
+        )
+        .bind(format!("-{} minutes", config.confirm_minutes))
+        .execute(conn)
+        .await?
+        .rows_affected())
+    }
+
+    /// Whether the address already holds `max_pending` unconfirmed bookings, or the client
+    /// `client_max_pending`. Every pending booking holds a slot, so without a cap anyone could
+    /// hold all of them.
+    pub async fn too_many_pending(
+        conn: &mut sqlx::SqliteConnection,
+        config: &BookingConfig,
+        email: &str,
+        client_ip: Option<IpAddr>,
+    ) -> Result<bool, sqlx::Error> {
+        let for_address: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM bookings WHERE status = 'pending' AND email = $1")
+            .bind(email)
+            .fetch_one(&mut *conn)
+            .await?;
+        let for_client: i64 = match client_ip {
+            Some(ip) => sqlx::query_scalar(
+                "SELECT COUNT(*) FROM bookings JOIN consents ON consents.booking_id = bookings.id \
+                 WHERE bookings.status = 'pending' AND consents.ip_hash = $1",
+            )
+            .bind(crate::terms::ssr::hash_ip(ip))
+            .fetch_one(&mut *conn)
+            .await?,
+            None => 0,
+        };
+        Ok(for_address >= config.max_pending || for_client >= config.client_max_pending)
+    }
+
+    /// The bookable slots from `now` on, grouped by day. Days without free slots are left out.
+    pub async fn available_slots(
+        conn: &mut sqlx::SqliteConnection,
+        config: &BookingConfig,
+        now: NaiveDateTime,
+    ) -> Result<Vec<DaySlots>, sqlx::Error> {
+        let hours: Vec<OpeningHours> = sqlx::query_as("SELECT weekday, start_time, end_time FROM booking_hours")
+            .fetch_all(&mut *conn)
+            .await?;
+        let closed: HashSet<NaiveDate> = sqlx::query_scalar("SELECT date FROM booking_closed_dates WHERE date >= $1")
+            .bind(now.date())
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .collect();
+        // Unconfirmed bookings that have expired but not been released yet don't hold their slot
+        let taken: HashSet<NaiveDateTime> = sqlx::query_scalar(
+            "SELECT slot_start FROM bookings WHERE slot_start >= $1 \
+             AND (status = 'confirmed' OR (status = 'pending' AND created_at > datetime('now', $2)))",
+        )
+        .bind(now)
+        .bind(format!("-{} minutes", config.confirm_minutes))
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .collect();
+
+        let earliest = now + Duration::hours(config.min_notice_hours);
+        Ok(now
+            .date()
+            .iter_days()
+            .take(config.days_ahead as usize + 1)
+            .filter(|date| !closed.contains(date))
+            .map(|date| DaySlots {
+                date,
+                times: slot_starts(date, &hours, config.slot_minutes)
+                    .into_iter()
+                    .filter(|slot| *slot >= earliest && !taken.contains(slot))
+                    .map(|slot| slot.time())
+                    .collect(),
+            })
+            .filter(|day| !day.times.is_empty())
+            .collect())
+    }
+
+    /// True if `slot` is one of the slots offered by `available_slots`.
+    pub fn is_available(days: &[DaySlots], slot: NaiveDateTime) -> bool {
+        days.iter().any(|day| day.date == slot.date() && day.times.contains(&slot.time()))
+    }
+
+    pub fn now() -> NaiveDateTime {
+        chrono::Local::now().naive_local()
+    }
+
+    /// A `/booking/{id}` link that lets whoever has it perform `action` (`confirm` or `cancel`).
+    pub fn link(action: &str, id: i64) -> String {
+        let signature = signing::sign(LINK_PURPOSE, &format!("{}:{}", action, id));
+        format!("{}/booking/{}?action={}&sig={}", crate::mail::site_url(), id, action, signature)
+    }
+
+    pub fn verify_link(action: &str, id: i64, signature: &str) -> bool {
+        signing::verify(LINK_PURPOSE, &format!("{}:{}", action, id), signature)
+    }
+
+    /// For log lines, without the customer's details.
+    pub fn describe(id: i64, slot: NaiveDateTime) -> String {
+        format!("booking {} for {}", id, slot_value(slot))
+    }
+}
+
+const BOOKING_SUCCESS_MESSAGE: &str =
+    "Tack! Vi har skickat ett mejl till dig. Bekräfta bokningen via länken i mejlet, annars släpps tiden efter en stund.";
+
+#[island]
+pub fn BookingPicker(
+    days: Vec<DaySlots>,
+    initial_success: bool,
+    initial_error: Option<BookingError>,
+    form_token: String,
+) -> impl IntoView {
+    let book_action = ServerAction::<BookSlot>::new();
+    let is_pending = move || book_action.pending().get();
+
+    let displayed_success = RwSignal::new(initial_success);
+    let displayed_error = RwSignal::new(initial_error);
+
+    let slot_rw = RwSignal::new(String::new());
+    let email_rw = RwSignal::new(String::new());
+    let description_rw = RwSignal::new(String::new());
+    let terms_agreed_rw = RwSignal::new(false);
+    // Slots that turned out to be taken since the page was rendered, or that were just booked here
+    let taken_rw = RwSignal::new(HashSet::<String>::new());
+    let form_token_rw = RwSignal::new(form_token);
+    let form_ref: NodeRef<html::Form> = NodeRef::new();
+
+    // Only checked in the browser, without JS the button stays enabled and the server validates
+    let is_form_valid = RwSignal::new(true);
+    Effect::new(move |_| {
+        is_form_valid.set(
+            validate_booking(&slot_rw.get(), &email_rw.get(), &description_rw.get(), terms_agreed_rw.get()).is_ok(),
+        );
+    });
+
+    Effect::new(move |_| {
+        if let Some(result) = book_action.value().get() {
+            // The form token has been used up by the server
+            leptos::task::spawn_local(async move {
+                if let Ok(token) = crate::contact::get_form_token().await {
+                    form_token_rw.set(token);
+                }
+            });
+            let slot = slot_rw.get_untracked();
+            match result {
+                Ok(_) => {
+                    taken_rw.update(|taken| { taken.insert(slot); });
+                    displayed_success.set(true);
+                    displayed_error.set(None);
+                    slot_rw.set(String::new());
+                    description_rw.set(String::new());
+                    terms_agreed_rw.set(false);
+                }
+                Err(e) => {
+                    if e.errors().contains(&&BookingError::SlotTaken) {
+                        taken_rw.update(|taken| { taken.insert(slot); });
+                        slot_rw.set(String::new());
+                    }
+                    displayed_error.set(Some(e));
+                    displayed_success.set(false);
+                }
+            }
+        }
+    });
+
+    let has_error = move |variant: BookingError| {
+        displayed_error.get().is_some_and(|error| error.errors().contains(&&variant))
+    };
+    let field_error_message = move |variants: &[BookingError]| {
+        displayed_error.get().and_then(|error| {
+            error.errors().into_iter()
+                .find(|error| variants.contains(error))
+                .map(|error| error.get_user_message())
+        }).unwrap_or_default()
+    };
+    let is_general_error = move || {
+        displayed_error.get().is_some_and(|error| {
+            error.errors().iter().any(|error| !matches!(error,
+                BookingError::MissingSlot | BookingError::MissingEmail | BookingError::InvalidEmailFormat |
+                BookingError::EmailTooLong | BookingError::MissingDescription | BookingError::DescriptionTooLong |
+                BookingError::DescriptionTooLarge | BookingError::TermsNotAccepted))
+        })
+    };
+
+    let has_days = !days.is_empty();
+
+    // Signal to playwright to start
+    Effect::new(move |_| {
+        if let Some(el) = form_ref.get() {
+            el.set_attribute("data-testid", "booking-form").expect("could not set attribute");
+        }
+    });
+
+    view! {
+        <ActionForm action=book_action node_ref=form_ref>
+            <Show when=move || displayed_success.get()>
+                <p class="success-message">{BOOKING_SUCCESS_MESSAGE}</p>
+            </Show>
+            <Show when=is_general_error>
+                <p class="error-message general-error">
+                    {move || displayed_error.get().map(|e| e.get_user_message()).unwrap_or_default()}
+                </p>
+            </Show>
+
+            <fieldset class="booking-slots" aria-describedby="booking-slot-error">
+                <legend>"Välj en tid:"</legend>
+                {(!has_days).then(|| view! {
+                    <p>"Det finns inga lediga tider just nu. Skriv till oss i kontaktformuläret så hittar vi en tid."</p>
+                })}
+                {days.into_iter().enumerate().map(|(index, day)| {
+                    let date = day.date;
+                    view! {
+                        // Works without JS, the first day is open from the start
+                        <details class="booking-day" open=index == 0>
+                            <summary>{date_label(date)}</summary>
+                            <div class="booking-times">
+                                {day.times.into_iter().map(|time| {
+                                    let value = slot_value(date.and_time(time));
+                                    let id = format!("slot-{}", value.replace(':', ""));
+                                    let is_taken = {
+                                        let value = value.clone();
+                                        move || taken_rw.get().contains(&value)
+                                    };
+                                    let is_checked = {
+                                        let value = value.clone();
+                                        move || slot_rw.get() == value
+                                    };
+                                    let on_change = {
+                                        let value = value.clone();
+                                        move |_| slot_rw.set(value.clone())
+                                    };
+                                    view! {
+                                        <input
+                                            id=id.clone()
+                                            type="radio"
+                                            name="slot"
+                                            value=value
+                                            disabled=move || is_pending() || is_taken()
+                                            prop:checked=is_checked
+                                            on:change=on_change
+                                        />
+                                        <label for=id>{time.format("%H:%M").to_string()}</label>
+                                    }
+                                }).collect_view()}
+                            </div>
+                        </details>
+                    }
+                }).collect_view()}
+                <Show when=move || has_error(BookingError::MissingSlot)>
+                    <p class="error-message" id="booking-slot-error">{move || field_error_message(&[BookingError::MissingSlot])}</p>
+                </Show>
+            </fieldset>
+
+            <div class="form-field">
+                <label for="booking-description">"Vad behöver du hjälp med?"</label>
+                <textarea
+                    id="booking-description"
+                    name="description"
+                    disabled=is_pending
+                    bind:value=description_rw
+                    class:error=move || has_error(BookingError::MissingDescription) || has_error(BookingError::DescriptionTooLong) || has_error(BookingError::DescriptionTooLarge)
+                    aria-describedby="booking-description-error"
+                />
+                <Show when=move || has_error(BookingError::MissingDescription) || has_error(BookingError::DescriptionTooLong) || has_error(BookingError::DescriptionTooLarge)>
+                    <p class="error-message" id="booking-description-error">
+                        {move || field_error_message(&[BookingError::MissingDescription, BookingError::DescriptionTooLong, BookingError::DescriptionTooLarge])}
+                    </p>
+                </Show>
+            </div>
+
+            <div class="form-field">
+                <label for="booking-email">"Din email:"</label>
+                <input
+                    id="booking-email"
+                    type="email"
+                    name="email"
+                    disabled=is_pending
+                    bind:value=email_rw
+                    class:error=move || has_error(BookingError::MissingEmail) || has_error(BookingError::InvalidEmailFormat) || has_error(BookingError::EmailTooLong)
+                    aria-describedby="booking-email-error"
+                />
+                <Show when=move || has_error(BookingError::MissingEmail) || has_error(BookingError::InvalidEmailFormat) || has_error(BookingError::EmailTooLong)>
+                    <p class="error-message" id="booking-email-error">
+                        {move || field_error_message(&[BookingError::MissingEmail, BookingError::InvalidEmailFormat, BookingError::EmailTooLong])}
+                    </p>
+                </Show>
+            </div>
+
+            <div class="form-field honeypot" aria-hidden="true">
+                <label for="booking-website">"Lämna det här fältet tomt:"</label>
+                <input id="booking-website" type="text" name="website" tabindex="-1" autocomplete="off"/>
+            </div>
+            <input type="hidden" name="form_token" value=move || form_token_rw.get()/>
+
+            <div class="form-field terms">
+                <input
+                    id="booking-terms"
+                    type="checkbox"
+                    name="terms"
+                    value=BOOKING_TERMS.version.to_string()
+                    disabled=is_pending
+                    bind:checked=terms_agreed_rw
+                    class:error=move || has_error(BookingError::TermsNotAccepted)
+                    aria-describedby="booking-terms-error"
+                />
+                <label for="booking-terms">{BOOKING_TERMS.text}</label>
+                <Show when=move || has_error(BookingError::TermsNotAccepted)>
+                    <p class="error-message" id="booking-terms-error">{move || field_error_message(&[BookingError::TermsNotAccepted])}</p>
+                </Show>
+            </div>
+
+            <input
+                type="submit"
+                data-testid="booking-form-submit"
+                value=move || if is_pending() { "Bokar..." } else { "Boka" }
+                disabled=move || is_pending() || !is_form_valid.get()
+            />
+        </ActionForm>
+    }
+}
+
+#[component]
+pub fn BookingForm() -> impl IntoView {
+    let query_map = use_query_map();
+    let (initial_success, initial_error) = query_map.with(|params| {
+        (
+            params.get("booking").is_some_and(|status| status == "pending"),
+            params.get("booking_error").and_then(|error_str| BookingError::from_str(&error_str).ok()),
+        )
+    });
+
+    #[cfg(feature = "ssr")]
+    let form_token = crate::spam::issue_form_token();
+    #[cfg(not(feature = "ssr"))]
+    let form_token = String::new();
+
+    let days = Resource::new(|| (), |_| get_available_slots());
+
+    view! {
+        <Suspense fallback=|| view! { <p>"Hämtar lediga tider..."</p> }>
+            {move || {
+                let (initial_error, form_token) = (initial_error.clone(), form_token.clone());
+                Suspend::new(async move {
+                    match days.await {
+                        Ok(days) => view! {
+                            <BookingPicker days initial_success initial_error form_token/>
+                        }.into_any(),
+                        Err(e) => view! { <p class="error-message">{e.get_user_message()}</p> }.into_any(),
+                    }
+                })
+            }}
+        </Suspense>
+    }
+}
+
+/// `/booking/{id}?action=confirm|cancel&sig=…`, linked from the booking mail. Shows the booking
+/// and a button for the action; following the link alone changes nothing, since mail scanners
+/// open links too.
+#[component]
+pub fn BookingPage() -> impl IntoView {
+    let params = use_params_map();
+    let query_map = use_query_map();
+
+    let id = params.with(|params| params.get("id").and_then(|id| id.parse::<i64>().ok()).unwrap_or_default());
+    let (action, signature, error) = query_map.with(|params| {
+        (
+            params.get("action").unwrap_or_default(),
+            params.get("sig").unwrap_or_default(),
+            params.get("error").and_then(|error_str| BookingError::from_str(&error_str).ok()),
+        )
+    });
+
+    let booking = Resource::new(
+        move || (id, action.clone(), signature.clone()),
+        |(id, action, signature)| get_booking(id, action, signature),
+    );
+    let confirm_action = ServerAction::<ConfirmBooking>::new();
+    let cancel_action = ServerAction::<CancelBooking>::new();
+
+    view! {
+        <div id="booking-details">
+            <h1>"Din bokning"</h1>
+            {error.map(|e| view! { <p class="error-message">{e.get_user_message()}</p> })}
+            <Suspense fallback=|| view! { <p>"Laddar..."</p> }>
+                {move || Suspend::new(async move {
+                    let (action, signature) = query_map.with(|params| {
+                        (params.get("action").unwrap_or_default(), params.get("sig").unwrap_or_default())
+                    });
+                    match booking.await {
+                        Ok((booking, can_cancel)) => view! {
+                            <dl>
+                                <dt>"Tid"</dt>
+                                <dd>{slot_label(booking.slot_start)}</dd>
+                                <dt>"Status"</dt>
+                                <dd>{booking.status.label()}</dd>
+                                <dt>"Ärende"</dt>
+                                <dd>{booking.description}</dd>
+                            </dl>
+                            {(action == "confirm" && booking.status == BookingStatus::Pending).then(|| view! {
+                                <ActionForm action=confirm_action>
+                                    <input type="hidden" name="id" value=booking.id/>
+                                    <input type="hidden" name="sig" value=signature.clone()/>
+                                    <input type="submit" value="Bekräfta bokningen"/>
+                                </ActionForm>
+                            })}
+                            {(action == "cancel" && can_cancel).then(|| view! {
+                                <ActionForm action=cancel_action>
+                                    <input type="hidden" name="id" value=booking.id/>
+                                    <input type="hidden" name="sig" value=signature.clone()/>
+                                    <input type="submit" class="danger" value="Avboka"/>
+                                </ActionForm>
+                            })}
+                        }.into_any(),
+                        Err(e) => view! { <p class="error-message">{e.get_user_message()}</p> }.into_any(),
+                    }
+                })}
+            </Suspense>
+        </div>
+    }
+}
+
+#[server(GetAvailableSlots, "/api")]
+pub async fn get_available_slots() -> Result<Vec<DaySlots>, BookingError> {
+    use self::ssr::{available_slots, now, BookingConfig};
+    use crate::app::ssr::db;
+
+    let mut conn = db().await?;
+    Ok(available_slots(&mut conn, &BookingConfig::from_env(), now()).await?)
+}
+
+/// Reserves a slot. The unique index on active bookings makes sure that two customers can't get
+/// the same slot, whichever insert comes second fails.
+#[server(BookSlot, "/api")]
+pub async fn book_slot(
+    slot: Option<String>,
+    email: String,
+    description: String,
+    terms: Option<String>,
+    website: Option<String>,
+    form_token: Option<String>,
+) -> Result<(), BookingError> {
+    use self::ssr::{available_slots, describe, is_available, link, now, release_unconfirmed, too_many_pending, BookingConfig};
+    use crate::app::ssr::db;
+    use crate::validation::normalise_email;
+    use leptos::logging::log;
+    use leptos_axum::redirect;
+    use sqlx::Connection;
+
+    let fail = |error: BookingError| -> Result<(), BookingError> {
+        redirect(&format!("/?booking_error={}#booking", error));
+        Err(error)
+    };
+
+    // The same budget as the contact form, a booking sends mail too
+    if !crate::rate_limit::check_contact_form().await {
+        log!("Rate limit exceeded for booking");
+        return fail(BookingError::RateLimited);
+    }
+    if let Err(error) = crate::spam::check_submission(website.as_deref(), form_token.as_deref(), false).await {
+        log!("Spam check failed for booking: {}", error);
+        return fail(error.into());
+    }
+
+    let slot = match validate_booking(slot.as_deref().unwrap_or_default(), &email, &description, terms.is_some()) {
+        Ok(slot) => slot,
+        Err(error) => {
+            log!("Booking validation failed: {}", error);
+            return fail(error);
+        }
+    };
+    let email = normalise_email(&email).unwrap_or_else(|_| email.trim().to_string());
+    let description = description.trim();
+
+    let domain = email.rsplit_once('@').map(|(_, domain)| domain).unwrap_or_default();
+    match crate::blocklist::is_blocked(domain).await {
+        Ok(true) => {
+            log!("Rejected booking from blocked domain: {}", domain);
+            return fail(BookingError::DomainNotAllowed);
+        }
+        Ok(false) => {}
+        Err(e) => log!("Could not check blocked domains: {}", e),
+    }
+
+    let config = BookingConfig::from_env();
+    let client_ip = crate::rate_limit::client_ip().await;
+    let mut conn = db().await?;
+    let mut tx = conn.begin().await?;
+
+    release_unconfirmed(&mut *tx, &config).await?;
+    if too_many_pending(&mut *tx, &config, &email, client_ip).await? {
+        log!("Booking refused, too many unconfirmed bookings from the same address or client.");
+        return fail(BookingError::TooManyPending);
+    }
+    if !is_available(&available_slots(&mut *tx, &config, now()).await?, slot) {
+        return fail(BookingError::SlotUnavailable);
+    }
+
+    let inserted = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO bookings (slot_start, email, description) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(slot)
+    .bind(&email)
+    .bind(description)
+    .fetch_one(&mut *tx)
+    .await;
+    let id = match inserted {
+        Ok(id) => id,
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            log!("Slot {} was taken by a concurrent booking", slot_value(slot));
+            return fail(BookingError::SlotTaken);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    crate::terms::ssr::record_for_booking(&mut tx, id, terms.as_deref().unwrap_or_default(), client_ip).await?;
+    crate::mail::send_booking_request(&mut tx, &email, &slot_label(slot), config.confirm_minutes, &link("confirm", id), &link("cancel", id))
+        .await?;
+    tx.commit().await?;
+    crate::jobs::wake();
+
+    log!("Reserved {}, waiting for confirmation.", describe(id, slot));
+
+    // The island shows the result itself, only browsers without JS are sent back to the page
+    let headers: axum::http::HeaderMap = leptos_axum::extract().await?;
+    let accepts_json = headers
+        .get("Accept")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|s| s.contains("application/json"));
+    if !accepts_json {
+        redirect("/?booking=pending#booking");
+    }
+    Ok(())
+}
+
+/// Returns the booking, and whether it can still be cancelled, if `signature` is valid for `action`.
+#[server(GetBooking, "/api")]
+pub async fn get_booking(id: i64, action: String, signature: String) -> Result<(Booking, bool), BookingError> {
+    use self::ssr::{now, verify_link};
+    use crate::app::ssr::db;
+
+    if !verify_link(&action, id, &signature) {
+        return Err(BookingError::InvalidLink);
+    }
+
+    let mut conn = db().await?;
+    let booking = sqlx::query_as::<_, Booking>(
+        "SELECT id, slot_start, email, description, status, created_at, confirmed_at, cancelled_at FROM bookings WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&mut conn)
+    .await?
+    .ok_or(BookingError::InvalidLink)?;
+
+    let can_cancel = booking.status != BookingStatus::Cancelled && booking.slot_start > now();
+    Ok((booking, can_cancel))
+}
+
+#[server(ConfirmBooking, "/api")]
+pub async fn confirm_booking(id: i64, sig: String) -> Result<(), BookingError> {
+    use self::ssr::{describe, verify_link, BookingConfig};
+    use crate::app::ssr::db;
+    use leptos::logging::log;
+    use leptos_axum::redirect;
+    use sqlx::Connection;
+
+    let page = format!("/booking/{}?action=confirm&sig={}", id, sig);
+    if !verify_link("confirm", id, &sig) {
+        let error = BookingError::InvalidLink;
+        redirect(&format!("{}&error={}", page, error));
+        return Err(error);
+    }
+
+    let config = BookingConfig::from_env();
+    let mut conn = db().await?;
+    // The notification is queued together with the change, so it can't be lost to a restart
+    let mut tx = conn.begin().await?;
+    let confirmed = sqlx::query_as::<_, (NaiveDateTime, String, String)>(
+        "UPDATE bookings SET status = 'confirmed', confirmed_at = CURRENT_TIMESTAMP \
+         WHERE id = $1 AND status = 'pending' AND created_at > datetime('now', $2) \
+         RETURNING slot_start, email, description",
+    )
+    .bind(id)
+    .bind(format!("-{} minutes", config.confirm_minutes))
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    match confirmed {
+        Some((slot, email, description)) => {
+            crate::mail::notify_booking_confirmed(&mut tx, id, &email, &description, &slot_label(slot)).await?;
+            tx.commit().await?;
+            crate::jobs::wake();
+            log!("Confirmed {}.", describe(id, slot));
+        }
+        // Following the link twice is fine
+        None => {
+            let status: Option<BookingStatus> = sqlx::query_scalar("SELECT status FROM bookings WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            if status != Some(BookingStatus::Confirmed) {
+                let error = BookingError::ConfirmationExpired;
+                redirect(&format!("{}&error={}", page, error));
+                return Err(error);
+            }
+        }
+    }
+
+    redirect(&page);
+    Ok(())
+}
+
+#[server(CancelBooking, "/api")]
+pub async fn cancel_booking(id: i64, sig: String) -> Result<(), BookingError> {
+    use self::ssr::{describe, now, verify_link};
+    use crate::app::ssr::db;
+    use leptos::logging::log;
+    use leptos_axum::redirect;
+    use sqlx::Connection;
+
+    let page = format!("/booking/{}?action=cancel&sig={}", id, sig);
+    if !verify_link("cancel", id, &sig) {
+        let error = BookingError::InvalidLink;
+        redirect(&format!("{}&error={}", page, error));
+        return Err(error);
+    }
+
+    let mut conn = db().await?;
+    let mut tx = conn.begin().await?;
+    let cancelled = sqlx::query_as::<_, (NaiveDateTime, String, bool)>(
+        "UPDATE bookings SET status = 'cancelled', cancelled_at = CURRENT_TIMESTAMP \
+         WHERE id = $1 AND status != 'cancelled' AND slot_start > $2 \
+         RETURNING slot_start, email, confirmed_at IS NOT NULL",
+    )
+    .bind(id)
+    .bind(now())
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    match cancelled {
+        Some((slot, email, was_confirmed)) => {
+            // An unconfirmed booking was never announced
+            if was_confirmed {
+                crate::mail::notify_booking_cancelled(&mut tx, id, &email, &slot_label(slot)).await?;
+            }
+            tx.commit().await?;
+            crate::jobs::wake();
+            log!("Cancelled {} on the customer's request.", describe(id, slot));
+        }
+        None => {
+            let status: Option<BookingStatus> = sqlx::query_scalar("SELECT status FROM bookings WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            if status != Some(BookingStatus::Cancelled) {
+                let error = BookingError::TooLateToCancel;
+                redirect(&format!("{}&error={}", page, error));
+                return Err(error);
+            }
+        }
+    }
+
+    redirect(&page);
+    Ok(())
+}
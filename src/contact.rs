@@ -1,23 +1,82 @@
 use leptos::{html, prelude::*};
+use leptos::server_fn::codec::{MultipartData, MultipartFormData};
+use leptos::server_fn::ServerFn;
+use leptos::web_sys::FormData;
 use leptos_router::hooks::use_query_map;
 use crate::error::ContactFormError;
+use crate::inquiry::{BudgetRange, ServiceType};
+use crate::pow::PowChallenge;
+use crate::terms::CONTACT_TERMS;
+use crate::validation::{
+    message_length, normalise_email, parse_deadline, today, validate_contact_form, validate_email, validate_message,
+    ContactFormFields, ATTACHMENT_ACCEPT, MESSAGE_MAX_LENGTH,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::str::FromStr;
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ContactFormData {
     message: String,
     email: String,
+    service_type: String,
+    budget: String, // Optional fields are sent empty.
+    deadline: String,
+    company: String,
     terms: Option<String>, // Checkboxes send "on" (or value) if checked, nothing if not.
+    website: Option<String>, // Honeypot, must be empty.
+    form_token: Option<String>,
+    pow_challenge: Option<String>, // Only sent by browsers with JS.
+    pow_solution: Option<String>,
+    idempotency_key: Option<String>, // Repeated by retries of the same submission.
+}
+
+impl ContactFormData {
+    /// Picks the fields out of a multipart form, attachments are read separately.
+    pub fn from_fields(mut fields: HashMap<String, String>) -> Self {
+        Self {
+            message: fields.remove("message").unwrap_or_default(),
+            email: fields.remove("email").unwrap_or_default(),
+            service_type: fields.remove("service_type").unwrap_or_default(),
+            budget: fields.remove("budget").unwrap_or_default(),
+            deadline: fields.remove("deadline").unwrap_or_default(),
+            company: fields.remove("company").unwrap_or_default(),
+            terms: fields.remove("terms"),
+            website: fields.remove("website"),
+            form_token: fields.remove("form_token"),
+            pow_challenge: fields.remove("pow_challenge"),
+            pow_solution: fields.remove("pow_solution"),
+            idempotency_key: fields.remove("idempotency_key"),
+        }
+    }
+}
+
+/// Progress of the proof of work that the island solves in the background.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PowState {
+    /// Server side rendering, or JS not running yet.
+    NotStarted,
+    Solving,
+    Solved { challenge: String, solution: u64 },
+    /// No challenge could be fetched. The form is still sent, the server then requires a longer fill time.
+    Unavailable,
+}
+
+/// The reference number shown to the customer for an inquiry, based on the `emails.id` primary key.
+pub fn reference_number(id: i64) -> String {
+    format!("TIT-{:06}", id)
 }
 
 #[island]
 pub fn InteractiveContactForm(
     initial_success_message: Option<String>,
-    initial_error: Option<ContactFormError>
+    initial_error: Option<ContactFormError>,
+    form_token: String,
+    idempotency_key: String,
 ) -> impl IntoView {
-    // Form Submit Action
-    let submit_action = ServerAction::<StoreContactForm>::new();
+    // Form Submit Action. Multipart (for the attachments) isn't supported by ActionForm, so the
+    // form is submitted by hand. FormData isn't Send, hence a local action.
+    let submit_action = Action::new_local(|data: &FormData| store_contact_form(data.clone().into()));
     let is_pending = move || submit_action.pending().get();
 
     // Signals for displaying success/error messages, initialized from props
@@ -27,26 +86,66 @@ pub fn InteractiveContactForm(
     // Form field states
     let message_rw = RwSignal::new(String::default());
     let email_rw = RwSignal::new(String::default());
+    let service_type_rw = RwSignal::new(String::default());
+    let budget_rw = RwSignal::new(String::default());
+    let deadline_rw = RwSignal::new(String::default());
+    let company_rw = RwSignal::new(String::default());
     let terms_agreed_rw = RwSignal::new(false);
+    // Messages sent from this page, so that the next message gets a key of its own
+    let sent_count_rw = RwSignal::new(0u32);
+    // A token can only be sent once, a new one is fetched after every submission
+    let form_token_rw = RwSignal::new(form_token);
     let form_ref: NodeRef<html::Form> = NodeRef::new();
     let message_touched_rw = RwSignal::new(false); 
     let email_touched_rw = RwSignal::new(false);   
 
-    // Client-side validation signals
-    let message_is_empty = Signal::derive(move || message_rw.get().trim().is_empty());
-    let email_is_invalid_format = Signal::derive(move || {
-        let email_val = email_rw.get();
-        email_val.is_empty() || !email_val.contains('@') 
-    });
+    // Client-side validation signals, same rules as on the server
+    let message_client_error = Signal::derive(move || validate_message(&message_rw.get()).err());
+    let email_client_error = Signal::derive(move || validate_email(&email_rw.get()).err());
+    let deadline_client_error = Signal::derive(move || parse_deadline(&deadline_rw.get(), today()).err());
     let is_form_valid = RwSignal::new(true); 
 
+    // Proof of work, solved while the user types. A new challenge is needed after every submission.
+    let pow_state = RwSignal::new(PowState::NotStarted);
+    let pow_round = RwSignal::new(0u32);
     Effect::new(move |_| {
-        is_form_valid.set( !message_is_empty.get() && !email_is_invalid_format.get() && terms_agreed_rw.get() );
+        let round = pow_round.get();
+        pow_state.set(PowState::Solving);
+        leptos::task::spawn_local(async move {
+            let state = match get_pow_challenge().await {
+                Ok(PowChallenge { challenge, difficulty }) => {
+                    let solution = crate::pow::solve(&challenge, difficulty).await;
+                    PowState::Solved { challenge, solution }
+                }
+                Err(_) => PowState::Unavailable,
+            };
+            // A newer round may have started while solving
+            if pow_round.get_untracked() == round {
+                pow_state.set(state);
+            }
+        });
+    });
+    let is_pow_solving = move || pow_state.get() == PowState::Solving;
+
+    Effect::new(move |_| {
+        let (message, email, service_type, budget, deadline, company) =
+            (message_rw.get(), email_rw.get(), service_type_rw.get(), budget_rw.get(), deadline_rw.get(), company_rw.get());
+        let fields = ContactFormFields {
+            message: &message,
+            email: &email,
+            terms_accepted: terms_agreed_rw.get(),
+            service_type: &service_type,
+            budget: &budget,
+            deadline: &deadline,
+            company: &company,
+        };
+        is_form_valid.set(validate_contact_form(&fields, today()).is_ok());
     });
 
     // Show/hide client-side validation hints
-    let show_message_hint = Signal::derive(move || message_touched_rw.get() && message_is_empty.get());
-    let show_email_hint = Signal::derive(move || email_touched_rw.get() && email_is_invalid_format.get());
+    let message_remaining = Signal::derive(move || MESSAGE_MAX_LENGTH as i64 - message_length(&message_rw.get()) as i64);
+    let show_message_hint = Signal::derive(move || message_touched_rw.get() && message_client_error.get().is_some());
+    let show_email_hint = Signal::derive(move || email_touched_rw.get() && email_client_error.get().is_some());
 
     // Effect to handle ServerAction results
     Effect::new(move |_| {
@@ -54,26 +153,42 @@ pub fn InteractiveContactForm(
             match result {
                 Ok(_) => {
                     // Though success often means redirect, this handles cases where action resolves successfully client-side.
+                    // Resetting the form clears the picked files, the signals below clear the rest.
+                    if let Some(form) = form_ref.get_untracked() {
+                        form.reset();
+                    }
                     displayed_success_message.set("Ditt meddelande är mottaget. Vi återkopplar snart.".to_string());
                     displayed_error.set(None);
                     // Clear form fields and touched states
                     message_rw.set(String::new());
                     email_rw.set(String::new());
+                    service_type_rw.set(String::new());
+                    budget_rw.set(String::new());
+                    deadline_rw.set(String::new());
+                    company_rw.set(String::new());
                     terms_agreed_rw.set(false);
                     message_touched_rw.set(false);
                     email_touched_rw.set(false);
+                    sent_count_rw.update(|count| *count += 1);
                 }
                 Err(e) => {
                     displayed_error.set(Some(e));
                     displayed_success_message.set(String::new());
                 }
             }
+            // The challenge and the form token have been used up by the server
+            pow_round.update(|round| *round += 1);
+            leptos::task::spawn_local(async move {
+                if let Ok(token) = get_form_token().await {
+                    form_token_rw.set(token);
+                }
+            });
         }
     });
 
     // Effect to clear success/error messages when user starts typing again
-    Effect::new(move |prev_inputs: Option<(String, String, bool)>| {
-        let current_inputs = (message_rw.get(), email_rw.get(), terms_agreed_rw.get());
+    Effect::new(move |prev_inputs: Option<(String, String, bool, String, String)>| {
+        let current_inputs = (message_rw.get(), email_rw.get(), terms_agreed_rw.get(), service_type_rw.get(), deadline_rw.get());
         if let Some(previous) = prev_inputs { // Only run if not the first time (prev_inputs is populated)
             if previous != current_inputs { // Only run if inputs actually changed
                 if displayed_error.get_untracked().is_some() {
@@ -84,12 +199,21 @@ pub fn InteractiveContactForm(
         current_inputs // Return current_inputs to be used as prev_inputs in the next iteration
     });
 
-    // Helper to check if the current displayed_error matches a specific field error variant.
+    // Helper to check if the current displayed_error contains a specific field error variant.
     let is_field_error_variant = move |expected_variant_discriminant: std::mem::Discriminant<ContactFormError>| {
         displayed_error.get().map_or(false, |current_error| {
-            std::mem::discriminant(&current_error) == expected_variant_discriminant
+            current_error.errors().iter().any(|error| std::mem::discriminant(*error) == expected_variant_discriminant)
         })
     };
+
+    // The message of the first displayed error that belongs to a field, given its variants.
+    let field_error_message = move |variants: &[ContactFormError]| {
+        displayed_error.get().and_then(|current_error| {
+            current_error.errors().into_iter()
+                .find(|error| variants.iter().any(|variant| std::mem::discriminant(*error) == std::mem::discriminant(variant)))
+                .map(|error| error.get_user_message())
+        }).unwrap_or_default()
+    };
     
     // Signal to playwright to start
     Effect::new(move |_| {
@@ -99,7 +223,18 @@ pub fn InteractiveContactForm(
     });
 
     view! {
-        <ActionForm action=submit_action node_ref=form_ref>
+        <form
+            node_ref=form_ref
+            method="post"
+            action=StoreContactForm::url()
+            enctype="multipart/form-data"
+            on:submit=move |ev| {
+                ev.prevent_default();
+                if let Some(data) = form_ref.get().and_then(|form| FormData::new_with_form(&form).ok()) {
+                    submit_action.dispatch_local(data);
+                }
+            }
+        >
             <Show when=move || !displayed_success_message.get().is_empty()>
                 <p class="success-message">{move || displayed_success_message.get()}</p>
             </Show>
@@ -114,7 +249,15 @@ pub fn InteractiveContactForm(
                              !is_field_error_variant(std::mem::discriminant(&ContactFormError::EmailTooLong)) &&
                              !is_field_error_variant(std::mem::discriminant(&ContactFormError::MissingMessage)) &&
                              !is_field_error_variant(std::mem::discriminant(&ContactFormError::MessageTooLong)) &&
-                             !is_field_error_variant(std::mem::discriminant(&ContactFormError::TermsNotAccepted))
+                             !is_field_error_variant(std::mem::discriminant(&ContactFormError::MessageTooLarge)) &&
+                             !is_field_error_variant(std::mem::discriminant(&ContactFormError::TermsNotAccepted)) &&
+                             !is_field_error_variant(std::mem::discriminant(&ContactFormError::MissingServiceType)) &&
+                             !is_field_error_variant(std::mem::discriminant(&ContactFormError::InvalidBudget)) &&
+                             !is_field_error_variant(std::mem::discriminant(&ContactFormError::InvalidDeadline)) &&
+                             !is_field_error_variant(std::mem::discriminant(&ContactFormError::CompanyTooLong)) &&
+                             !is_field_error_variant(std::mem::discriminant(&ContactFormError::AttachmentTooLarge)) &&
+                             !is_field_error_variant(std::mem::discriminant(&ContactFormError::AttachmentTypeNotAllowed)) &&
+                             !is_field_error_variant(std::mem::discriminant(&ContactFormError::TooManyAttachments))
                     }
                 })
             }>
@@ -125,15 +268,38 @@ pub fn InteractiveContactForm(
 
             <Show when=move || cfg!(debug_assertions)>
                 <div>"Message Touched: " {move || message_touched_rw.get().to_string()}</div>
-                <div>"Message Empty: " {move || message_is_empty.get().to_string()}</div>
+                <div>"Message Error: " {move || format!("{:?}", message_client_error.get())}</div>
                 <div>"Email Touched: " {move || email_touched_rw.get().to_string()}</div>
-                <div>"Email Invalid Format: " {move || email_is_invalid_format.get().to_string()}</div>
+                <div>"Email Error: " {move || format!("{:?}", email_client_error.get())}</div>
                 <div>"Terms Agreed: " {move || terms_agreed_rw.get().to_string()}</div>
                 <div>"Is Form Valid: " {move || is_form_valid.get().to_string()}</div>
                 <div>"Displayed Error: " {move || format!("{:?}", displayed_error.get())}</div>
                 <div>"Displayed Success: " {move || displayed_success_message.get()}</div>
             </Show>
 
+            // Service Field
+            <div class="form-field">
+                <label for="service_type">"Vad gäller det?"</label>
+                <select
+                    id="service_type"
+                    name="service_type"
+                    disabled=is_pending
+                    bind:value=service_type_rw
+                    class:error=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::MissingServiceType))
+                    aria-describedby="service-type-error"
+                >
+                    <option value="">"Välj tjänst..."</option>
+                    {ServiceType::ALL.iter().map(|service| view! {
+                        <option value=service.as_str()>{service.label()}</option>
+                    }).collect_view()}
+                </select>
+                <Show when=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::MissingServiceType))>
+                    <p class="error-message" id="service-type-error">
+                        {move || field_error_message(&[ContactFormError::MissingServiceType])}
+                    </p>
+                </Show>
+            </div>
+
             // Message Field
             <div class="form-field">
                 <label for="message">"Skriv ett meddelande:"</label>
@@ -144,20 +310,26 @@ pub fn InteractiveContactForm(
                     disabled=is_pending
                     bind:value=message_rw
                     on:blur=move |_| message_touched_rw.set(true)
-                    class:error=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::MissingMessage)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::MessageTooLong))
-                    aria-invalid=move || (is_field_error_variant(std::mem::discriminant(&ContactFormError::MissingMessage)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::MessageTooLong))).to_string()
+                    class:error=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::MissingMessage)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::MessageTooLong)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::MessageTooLarge))
+                    aria-invalid=move || (is_field_error_variant(std::mem::discriminant(&ContactFormError::MissingMessage)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::MessageTooLong)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::MessageTooLarge))).to_string()
                     aria-describedby="message-error"
                 />
                 // Server error
-                <Show when=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::MissingMessage)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::MessageTooLong))>
+                <Show when=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::MissingMessage)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::MessageTooLong)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::MessageTooLarge))>
                     <p class="error-message" id="message-error">
-                       {move || displayed_error.get().map(|e| e.get_user_message()).unwrap_or_default()}
+                       {move || field_error_message(&[ContactFormError::MissingMessage, ContactFormError::MessageTooLong, ContactFormError::MessageTooLarge])}
                     </p>
                 </Show>
                 // Client error
                 <Show when=move || show_message_hint.get()>
-                     <p class="hint-message">"Meddelandet får inte vara tomt."</p>
+                     <p class="hint-message">{move || message_client_error.get().map(|e| e.get_user_message()).unwrap_or_default()}</p>
                 </Show>
+                <p class="char-counter" class:over-limit=move || message_remaining.get() < 0 aria-live="polite">
+                    {move || {
+                        let remaining = message_remaining.get();
+                        if remaining < 0 { format!("{} tecken för mycket", -remaining) } else { format!("{} tecken kvar", remaining) }
+                    }}
+                </p>
             </div>
 
             // Email Field
@@ -165,7 +337,10 @@ pub fn InteractiveContactForm(
                 <label for="email">"Ange din email:"</label>
                 <input
                     id="email"
-                    type="email"
+                    // Not type="email", browsers reject internationalised addresses that are valid
+                    type="text"
+                    inputmode="email"
+                    autocomplete="email"
                     name="email"
                     placeholder="Din email..."
                     disabled=is_pending
@@ -178,31 +353,132 @@ pub fn InteractiveContactForm(
                 // Server error
                  <Show when=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::InvalidEmailFormat)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::MissingEmail)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::EmailTooLong))>
                     <p class="error-message" id="email-error">
-                        { move || displayed_error.get().map(|e| e.get_user_message()).unwrap_or_default() }
+                        {move || field_error_message(&[ContactFormError::MissingEmail, ContactFormError::InvalidEmailFormat, ContactFormError::EmailTooLong])}
                     </p>
                 </Show>
                 // Client error
                 <Show when=move || show_email_hint.get()>
-                     <p class="hint-message">"Ange en giltig email."</p>
+                     <p class="hint-message">{move || email_client_error.get().map(|e| e.get_user_message()).unwrap_or_default()}</p>
+                </Show>
+            </div>
+
+            // Quote details, all optional
+            <div class="form-field">
+                <label for="company">"Företag (valfritt):"</label>
+                <input
+                    id="company"
+                    type="text"
+                    name="company"
+                    autocomplete="organization"
+                    disabled=is_pending
+                    bind:value=company_rw
+                    class:error=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::CompanyTooLong))
+                    aria-describedby="company-error"
+                />
+                <Show when=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::CompanyTooLong))>
+                    <p class="error-message" id="company-error">
+                        {move || field_error_message(&[ContactFormError::CompanyTooLong])}
+                    </p>
                 </Show>
             </div>
 
+            <div class="form-field">
+                <label for="budget">"Ungefärlig budget (valfritt):"</label>
+                <select
+                    id="budget"
+                    name="budget"
+                    disabled=is_pending
+                    bind:value=budget_rw
+                    class:error=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::InvalidBudget))
+                    aria-describedby="budget-error"
+                >
+                    <option value="">"Ingen uppgift"</option>
+                    {BudgetRange::ALL.iter().map(|budget| view! {
+                        <option value=budget.as_str()>{budget.label()}</option>
+                    }).collect_view()}
+                </select>
+                <Show when=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::InvalidBudget))>
+                    <p class="error-message" id="budget-error">
+                        {move || field_error_message(&[ContactFormError::InvalidBudget])}
+                    </p>
+                </Show>
+            </div>
+
+            <div class="form-field">
+                <label for="deadline">"Önskat klart senast (valfritt):"</label>
+                <input
+                    id="deadline"
+                    type="date"
+                    name="deadline"
+                    disabled=is_pending
+                    bind:value=deadline_rw
+                    class:error=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::InvalidDeadline))
+                    aria-describedby="deadline-error"
+                />
+                <Show when=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::InvalidDeadline))>
+                    <p class="error-message" id="deadline-error">
+                        {move || field_error_message(&[ContactFormError::InvalidDeadline])}
+                    </p>
+                </Show>
+                // Client error
+                <Show when=move || deadline_client_error.get().is_some()>
+                     <p class="hint-message">{move || deadline_client_error.get().map(|e| e.get_user_message()).unwrap_or_default()}</p>
+                </Show>
+            </div>
+
+            // Attachments
+            <div class="form-field">
+                <label for="attachments">"Bifoga filer (valfritt):"</label>
+                <input
+                    id="attachments"
+                    type="file"
+                    name="attachments"
+                    accept=ATTACHMENT_ACCEPT
+                    multiple
+                    disabled=is_pending
+                    class:error=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::AttachmentTooLarge)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::AttachmentTypeNotAllowed)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::TooManyAttachments))
+                    aria-describedby="attachments-error"
+                />
+                <p class="hint-message">"Till exempel en skärmdump av felmeddelandet. Högst 3 bilder eller PDF-filer på max 5 MB."</p>
+                <Show when=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::AttachmentTooLarge)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::AttachmentTypeNotAllowed)) || is_field_error_variant(std::mem::discriminant(&ContactFormError::TooManyAttachments))>
+                    <p class="error-message" id="attachments-error">
+                        {move || field_error_message(&[ContactFormError::AttachmentTooLarge, ContactFormError::AttachmentTypeNotAllowed, ContactFormError::TooManyAttachments])}
+                    </p>
+                </Show>
+            </div>
+
+            // Spam protection: a field hidden from humans that bots tend to fill in, and when the form was rendered
+            <div class="form-field honeypot" aria-hidden="true">
+                <label for="website">"Lämna det här fältet tomt:"</label>
+                <input id="website" type="text" name="website" tabindex="-1" autocomplete="off"/>
+            </div>
+            <input type="hidden" name="form_token" value=move || form_token_rw.get()/>
+            <input type="hidden" name="idempotency_key" value=move || format!("{}-{}", idempotency_key, sent_count_rw.get())/>
+            {move || match pow_state.get() {
+                PowState::Solved { challenge, solution } => Some(view! {
+                    <input type="hidden" name="pow_challenge" value=challenge/>
+                    <input type="hidden" name="pow_solution" value=solution.to_string()/>
+                }),
+                _ => None,
+            }}
+
              // Terms Checkbox
             <div class="form-field terms">
                 <input
                     id="terms"
                     type="checkbox"
                     name="terms" 
+                    value=CONTACT_TERMS.version.to_string()
                     disabled=is_pending
                     bind:checked=terms_agreed_rw
                     class:error=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::TermsNotAccepted))
                     aria-invalid=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::TermsNotAccepted)).to_string()
                     aria-describedby="terms-error"
                 />
-                <label for="terms">"Jag accepterar att informationen sparas. Uppgifterna tas bort efter slutfört ärende."</label> 
+                <label for="terms">{CONTACT_TERMS.text}</label> 
                  <Show when=move || is_field_error_variant(std::mem::discriminant(&ContactFormError::TermsNotAccepted))>
                     <p class="error-message" id="terms-error">
-                       {move || displayed_error.get().map(|e| e.get_user_message()).unwrap_or_default()}
+                       {move || field_error_message(&[ContactFormError::TermsNotAccepted])}
                     </p>
                 </Show>
             </div>
@@ -211,9 +487,9 @@ pub fn InteractiveContactForm(
                 type="submit"
                 data-testid="contact-form-submit"
                 value=move ||  if is_pending() { "Skickar..." } else { "Skicka" } 
-                disabled=move || is_pending() || !is_form_valid.get()
+                disabled=move || is_pending() || is_pow_solving() || !is_form_valid.get()
              />
-        </ActionForm>
+        </form>
      }
 }
 
@@ -229,20 +505,97 @@ pub fn ContactForm() -> impl IntoView {
         params.get("error").and_then(|error_str| ContactFormError::from_str(&error_str).ok())
     });
 
+    #[cfg(feature = "ssr")]
+    let (form_token, idempotency_key) = (crate::spam::issue_form_token(), crate::idempotency::new_key());
+    #[cfg(not(feature = "ssr"))]
+    let (form_token, idempotency_key) = (String::new(), String::new());
+
     view! {
-        <InteractiveContactForm initial_success_message=initial_success_message initial_error=initial_error />
+        <InteractiveContactForm initial_success_message=initial_success_message initial_error=initial_error form_token=form_token idempotency_key=idempotency_key />
     }
 }
 
-#[server(StoreContactForm, "/api")]
-pub async fn store_contact_form(
-    message: String,
-    email: String,
-    terms: Option<String>,
-) -> Result<(), ContactFormError> {
+#[server(GetPowChallenge, "/api")]
+pub async fn get_pow_challenge() -> Result<PowChallenge, ServerFnError> {
+    Ok(crate::pow::ssr::issue_challenge())
+}
+
+/// A new form token for an island that has used up the one it was rendered with.
+#[server(GetFormToken, "/api")]
+pub async fn get_form_token() -> Result<String, ServerFnError> {
+    Ok(crate::spam::issue_form_token())
+}
+
+/// Takes a multipart form with the `ContactFormData` fields and up to `MAX_ATTACHMENTS` files.
+#[server(name = StoreContactForm, prefix = "/api", input = MultipartFormData)]
+pub async fn store_contact_form(data: MultipartData) -> Result<(), ContactFormError> {
     use leptos_axum::{redirect, extract};
     use axum::http::HeaderMap;
     use leptos::logging::log;
+
+    // --- Rate limit ---
+    // Before the body is read, so that a client over the limit can't make us buffer attachments
+    #[cfg(feature = "ssr")]
+    if !crate::rate_limit::check_contact_form().await {
+        log!("Rate limit exceeded for contact form");
+        let error = ContactFormError::RateLimited;
+        redirect(&format!("/?error={}#contact", error.to_string()));
+        return Err(error);
+    }
+
+    // --- Reading the form ---
+    let (fields, attachments) = match crate::attachments::read_multipart(data).await {
+        Ok(form) => form,
+        Err(error) => {
+            log!("Could not read contact form: {}", error);
+            redirect(&format!("/?error={}#contact", error.to_string()));
+            return Err(error);
+        }
+    };
+    let ContactFormData {
+        message, email, service_type, budget, deadline, company, terms, website, form_token, pow_challenge, pow_solution,
+        idempotency_key,
+    } = ContactFormData::from_fields(fields);
+
+    // Check if "Accept"-header indicate this is a JSON-request (from a reactive client)
+    let headers: HeaderMap = extract().await.map_err(ServerFnError::from)?;
+    let accepts_json = headers
+        .get("Accept")
+        .and_then(|v| v.to_str().ok())
+        .map_or(false, |s| s.contains("application/json"));
+
+    // --- Repeated submissions ---
+    // Answered before the spam checks, a retry would fail them (the proof of work is used up)
+    #[cfg(feature = "ssr")]
+    let idempotency_key = crate::idempotency::parse_key(idempotency_key.as_deref()).map(str::to_string);
+    #[cfg(feature = "ssr")]
+    if let Some(key) = &idempotency_key {
+        // Hashed the way the submission would be stored
+        let email = normalise_email(email.trim()).unwrap_or_else(|_| email.trim().to_string());
+        let content_hash = crate::crypto::content_hash(&email, message.trim());
+        let existing = match crate::app::ssr::db().await {
+            Ok(mut conn) => crate::idempotency::find_by_key(&mut conn, key, &content_hash).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match existing {
+            Ok(Some((id, true))) => {
+                log!("Submission {} repeated, not stored again.", reference_number(id));
+                if !accepts_json {
+                    redirect("/?status=success#contact");
+                }
+                return Ok(());
+            }
+            Ok(Some((id, false))) => {
+                log!("Idempotency key of {} sent again with other content, rejected.", reference_number(id));
+                let error = ContactFormError::FormExpired;
+                redirect(&format!("/?error={}#contact", error.to_string()));
+                return Err(error);
+            }
+            Ok(None) => {}
+            // The unique index still keeps the key from being stored twice
+            Err(e) => log!("Could not look up idempotency key: {}", e),
+        }
+    }
         
     // --- Debug / Testing ---
     // Delay to make e2e testing stable.
@@ -252,49 +605,68 @@ pub async fn store_contact_form(
         sleep(Duration::from_millis(500)).await;
     }
 
-    
+    // --- Spam protection ---
+    #[cfg(feature = "ssr")]
+    {
+        let solved_challenge = match (pow_challenge.as_deref(), pow_solution.as_deref()) {
+            (Some(challenge), Some(solution)) => {
+                if let Err(error) = crate::pow::ssr::verify(challenge, solution).await {
+                    log!("Proof of work rejected: {}", error);
+                    redirect(&format!("/?error={}#contact", error.to_string()));
+                    return Err(error);
+                }
+                true
+            }
+            _ => false,
+        };
+
+        if let Err(error) = crate::spam::check_submission(website.as_deref(), form_token.as_deref(), solved_challenge).await {
+            log!("Spam check failed: {}", error);
+            redirect(&format!("/?error={}#contact", error.to_string()));
+            return Err(error);
+        }
+    }
+
     // --- Validation ---
     let email_trimmed = email.trim();
     let message_trimmed = message.trim();
 
-    if email_trimmed.is_empty() {
-        log!("Validation failed: MissingEmail");
-        let error = ContactFormError::MissingEmail;
-        redirect(&format!("/?error={}#contact", error.to_string()));
-        return Err(error);
-    }
-    if message_trimmed.is_empty() {
-        log!("Validation failed: MissingMessage");
-        let error = ContactFormError::MissingMessage;
-        redirect(&format!("/?error={}#contact", error.to_string()));
-        return Err(error);
-    }
-    if terms.is_none() { // "on" if checked, None if not.
-        log!("Validation failed: TermsNotAccepted");
-        let error = ContactFormError::TermsNotAccepted;
-        redirect(&format!("/?error={}#contact", error.to_string()));
-        return Err(error);
-    }
-    if email_trimmed.len() > 254 {
-        log!("Validation failed: EmailTooLong");
-        let error = ContactFormError::EmailTooLong;
-        redirect(&format!("/?error={}#contact", error.to_string()));
-        return Err(error);
-    }
-    if message_trimmed.len() > 5000 {
-        log!("Validation failed: MessageTooLong");
-        let error = ContactFormError::MessageTooLong;
-        redirect(&format!("/?error={}#contact", error.to_string()));
-        return Err(error);
-    }
-    if !email_trimmed.contains('@')
-        || email_trimmed.starts_with('@')
-        || email_trimmed.ends_with('@')
+    let fields = ContactFormFields {
+        message: message_trimmed,
+        email: email_trimmed,
+        terms_accepted: terms.is_some(), // "on" if checked, None if not.
+        service_type: &service_type,
+        budget: &budget,
+        deadline: &deadline,
+        company: &company,
+    };
+    let quote = match validate_contact_form(&fields, today()) {
+        Ok(quote) => quote,
+        Err(error) => {
+            log!("Validation failed: {}", error);
+            redirect(&format!("/?error={}#contact", error.to_string()));
+            return Err(error);
+        }
+    };
+
+    // Stored in canonical form, so that the same address is always spelled the same way
+    let email_normalised = normalise_email(email_trimmed).unwrap_or_else(|_| email_trimmed.to_string());
+
+    // --- Blocked domains ---
+    #[cfg(feature = "ssr")]
     {
-        log!("Validation failed: InvalidEmailFormat");
-        let error = ContactFormError::InvalidEmailFormat;
-        redirect(&format!("/?error={}#contact", error.to_string()));
-        return Err(error);
+        let domain = email_normalised.rsplit_once('@').map(|(_, domain)| domain).unwrap_or_default();
+        match crate::blocklist::is_blocked(domain).await {
+            Ok(true) => {
+                log!("Rejected submission from blocked domain: {}", domain);
+                let error = ContactFormError::DomainNotAllowed;
+                redirect(&format!("/?error={}#contact", error.to_string()));
+                return Err(error);
+            }
+            Ok(false) => {}
+            // Rather let a submission through than lose a real one
+            Err(e) => log!("Could not check blocked domains: {}", e),
+        }
     }
 
     // --- Database Operation ---
@@ -303,15 +675,6 @@ pub async fn store_contact_form(
     {
         use crate::app::ssr::db;
 
-        // Extract request HTTP-headers
-        let headers: HeaderMap = extract().await.map_err(ServerFnError::from)?;        
-
-        // Check if "Accept"-header indicate this is a JSON-request (from a reactive client)
-        let accepts_json = headers
-            .get("Accept")
-            .and_then(|v| v.to_str().ok())
-            .map_or(false, |s| s.contains("application/json"));
-
         let mut conn = match db().await {
             Ok(connection) => connection,
             Err(_) => {
@@ -320,13 +683,80 @@ pub async fn store_contact_form(
             }
         };
 
-        match sqlx::query("INSERT INTO emails (email, message) VALUES ($1, $2)")
-            .bind(email_trimmed)
-            .bind(message_trimmed)
-            .execute(&mut conn)
-            .await
+        let client_ip = crate::rate_limit::client_ip().await;
+
+        // The inquiry, its attachments, the consent and the jobs that follow it up are stored
+        // together or not at all
+        let inserted = async {
+            use sqlx::Connection;
+
+            let mut tx = conn.begin().await?;
+            if let Some(id) = crate::idempotency::find_duplicate(&mut *tx, &email_normalised, message_trimmed).await? {
+                return Ok(Err(id));
+            }
+            let (id, creation_date) = sqlx::query_as::<_, (i64, String)>(
+                "INSERT INTO emails (email, message, service_type, budget, deadline, company, idempotency_key, email_hash, content_hash) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id, creation_date",
+            )
+                .bind(crate::crypto::encrypt(crate::crypto::Field::Email, &email_normalised))
+                .bind(crate::crypto::encrypt(crate::crypto::Field::Message, message_trimmed))
+                .bind(quote.service_type)
+                .bind(quote.budget)
+                .bind(quote.deadline)
+                .bind(&quote.company)
+                .bind(&idempotency_key)
+                .bind(crate::crypto::email_hash(&email_normalised))
+                .bind(crate::crypto::content_hash(&email_normalised, message_trimmed))
+                .fetch_one(&mut *tx)
+                .await?;
+            crate::attachments::store(&mut tx, id, &attachments).await?;
+            crate::terms::ssr::record_for_inquiry(&mut tx, id, terms.as_deref().unwrap_or_default(), client_ip).await?;
+
+            // Follow-ups are queued with the inquiry, a failed send never fails the submission
+            crate::mail::notify_new_submission(&mut *tx, id, &email_normalised, message_trimmed, &creation_date, &quote).await?;
+            crate::mail::send_receipt(&mut *tx, id, &email_normalised, message_trimmed, client_ip).await?;
+            crate::webhooks::enqueue(
+                &mut *tx,
+                id,
+                "inquiry.created",
+                crate::webhooks::InquiryCreated {
+                    id,
+                    reference: reference_number(id),
+                    created_at: &creation_date,
+                    email: &email_normalised,
+                    message: message_trimmed,
+                    service_type: quote.service_type,
+                    budget: quote.budget,
+                    deadline: quote.deadline,
+                    company: quote.company.as_deref(),
+                },
+            )
+            .await?;
+            tx.commit().await?;
+            crate::jobs::wake();
+            // Err holds the inquiry that this one repeats
+            Ok::<Result<i64, i64>, sqlx::Error>(Ok(id))
+        }
+        .await;
+
+        match inserted
         {
-            Ok(_result) => {
+            Ok(Err(id)) => {
+                log!("Same message already received as {}, not stored again.", reference_number(id));
+                if !accepts_json {
+                    redirect("/?status=success#contact");
+                }
+                Ok(())
+            }
+            // A concurrent request with the same key got there first
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                log!("Submission repeated while being stored, not stored again.");
+                if !accepts_json {
+                    redirect("/?status=success#contact");
+                }
+                Ok(())
+            }
+            Ok(Ok(_)) => {
                 log!("Successfully inserted contact form data into DB.");
 
                 // If request is not from a reactive client (JS disabled in browser), redirect
@@ -0,0 +1,364 @@
+//! Encryption at rest for the `email` and `message` columns of `emails`, the rest of the
+//! conversation in `messages` and queued jobs.
+//!
+//! Values are encrypted with XChaCha20-Poly1305 under `ENCRYPTION_KEY` and stored as
+//! `enc:v1:<key id>:<hex nonce and ciphertext>`, where the key id is derived from the key. Keys
+//! that have been replaced go in `ENCRYPTION_OLD_KEYS` until `tivanderit reencrypt` has moved
+//! every row to the current key. Only the admin pages, and the portal for the person who
+//! sent an inquiry, decrypt.
+//!
+//! Encrypted values can't be compared in SQL, so rows also carry keyed hashes (`email_hash`,
+//! `content_hash`) for the lookups that need them. Client IP addresses are only stored as such
+//! hashes too. The hashes are keyed with `ENCRYPTION_INDEX_KEY`, which is never rotated: other
+//! tables (portal links, receipts, data requests) keep the hashes without the value, so they
+//! couldn't be recomputed under a new key.
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use leptos::logging::log;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use thiserror::Error;
+
+const PREFIX: &str = "enc:v1:";
+const NONCE_LENGTH: usize = 24;
+
+#[derive(Error, Debug)]
+pub enum CryptoError {
+    #[error("no key with id {0} is configured")]
+    UnknownKey(String),
+    #[error("value is malformed")]
+    Malformed,
+    #[error("value could not be decrypted, it has been altered or the key is wrong")]
+    Decrypt,
+}
+
+/// The column a value belongs to, bound into the ciphertext so that values can't be swapped
+/// between columns.
+#[derive(Clone, Copy, Debug)]
+pub enum Field {
+    Email,
+    Message,
+    /// `jobs.payload` and `dead_jobs.payload`, which contain addresses and messages.
+    JobPayload,
+    /// `messages.body`, the rest of the conversation about an inquiry.
+    MessageBody,
+}
+
+impl Field {
+    fn associated_data(&self) -> &'static [u8] {
+        match self {
+            Field::Email => b"emails.email",
+            Field::Message => b"emails.message",
+            Field::JobPayload => b"jobs.payload",
+            Field::MessageBody => b"messages.body",
+        }
+    }
+}
+
+struct Key {
+    id: String,
+    cipher: XChaCha20Poly1305,
+    /// What the hashes were keyed with before `ENCRYPTION_INDEX_KEY`, only used by `rehash`.
+    legacy_index_key: [u8; 32],
+}
+
+fn parse_key_bytes(hex_key: &str) -> Option<[u8; 32]> {
+    hex::decode(hex_key.trim()).ok()?.try_into().ok()
+}
+
+fn key_id(bytes: &[u8; 32]) -> String {
+    hex::encode(&Sha256::digest(bytes)[..4])
+}
+
+impl Key {
+    fn parse(hex_key: &str) -> Option<Key> {
+        let bytes = parse_key_bytes(hex_key)?;
+        Some(Key {
+            id: key_id(&bytes),
+            cipher: XChaCha20Poly1305::new(&bytes.into()),
+            legacy_index_key: Sha256::new().chain_update(b"blind-index").chain_update(bytes).finalize().into(),
+        })
+    }
+}
+
+struct Keys {
+    current: Key,
+    old: Vec<Key>,
+    index_key: [u8; 32],
+    index_key_id: String,
+}
+
+static KEYS: OnceLock<Keys> = OnceLock::new();
+
+fn keys() -> &'static Keys {
+    KEYS.get_or_init(|| {
+        let current = std::env::var("ENCRYPTION_KEY")
+            .ok()
+            .and_then(|key| Key::parse(&key))
+            .expect("ENCRYPTION_KEY must be set to 32 random bytes in hex (openssl rand -hex 32) - aborting startup because inquiries are stored encrypted");
+        let index_key = std::env::var("ENCRYPTION_INDEX_KEY")
+            .ok()
+            .and_then(|key| parse_key_bytes(&key))
+            .expect("ENCRYPTION_INDEX_KEY must be set to 32 random bytes in hex (openssl rand -hex 32), other than ENCRYPTION_KEY - aborting startup because stored hashes are keyed with it");
+        let old = std::env::var("ENCRYPTION_OLD_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|key| !key.trim().is_empty())
+            .filter_map(|key| {
+                let parsed = Key::parse(key);
+                if parsed.is_none() {
+                    log!("Ignoring an invalid key in ENCRYPTION_OLD_KEYS.");
+                }
+                parsed
+            })
+            .collect();
+        assert!(
+            key_id(&index_key) != current.id,
+            "ENCRYPTION_INDEX_KEY must not be the same as ENCRYPTION_KEY - aborting startup"
+        );
+        Keys { current, old, index_key_id: key_id(&index_key), index_key }
+    })
+}
+
+/// Fails early, at startup, if the key is missing instead of at the first submission.
+pub fn check_configuration() {
+    let keys = keys();
+    log!(
+        "Encrypting with key {} ({} old keys for decryption), hashing with key {}.",
+        keys.current.id,
+        keys.old.len(),
+        keys.index_key_id
+    );
+}
+
+pub fn encrypt(field: Field, plaintext: &str) -> String {
+    let key = &keys().current;
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let ciphertext = key
+        .cipher
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext.as_bytes(), aad: field.associated_data() })
+        .expect("encrypting to a Vec can't fail");
+    format!("{}{}:{}{}", PREFIX, key.id, hex::encode(nonce), hex::encode(ciphertext))
+}
+
+/// Decrypts a stored value. Values from before encryption was introduced are returned as they are.
+pub fn decrypt(field: Field, stored: &str) -> Result<String, CryptoError> {
+    let Some(rest) = stored.strip_prefix(PREFIX) else {
+        return Ok(stored.to_string());
+    };
+    let (key_id, data) = rest.split_once(':').ok_or(CryptoError::Malformed)?;
+    let data = hex::decode(data).map_err(|_| CryptoError::Malformed)?;
+    if data.len() < NONCE_LENGTH {
+        return Err(CryptoError::Malformed);
+    }
+
+    let keys = keys();
+    let key = std::iter::once(&keys.current)
+        .chain(&keys.old)
+        .find(|key| key.id == key_id)
+        .ok_or_else(|| CryptoError::UnknownKey(key_id.to_string()))?;
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+    let plaintext = key
+        .cipher
+        .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad: field.associated_data() })
+        .map_err(|_| CryptoError::Decrypt)?;
+    String::from_utf8(plaintext).map_err(|_| CryptoError::Decrypt)
+}
+
+/// For showing a value that can't be decrypted without failing the whole page.
+pub fn decrypt_or_placeholder(field: Field, stored: &str) -> String {
+    decrypt(field, stored).unwrap_or_else(|e| {
+        log!("Could not decrypt a stored {:?}: {}", field, e);
+        "[kunde inte dekrypteras]".to_string()
+    })
+}
+
+/// True if the value is encrypted with the current key.
+pub fn is_current(stored: &str) -> bool {
+    stored
+        .strip_prefix(PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .is_some_and(|(key_id, _)| key_id == keys().current.id)
+}
+
+fn blind_index_with(key: &[u8], purpose: &str, value: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(purpose.as_bytes());
+    mac.update(b":");
+    mac.update(value.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// A keyed hash for looking up rows by an encrypted value.
+fn blind_index(purpose: &str, value: &str) -> String {
+    blind_index_with(&keys().index_key, purpose, value)
+}
+
+/// Addresses are compared case insensitively.
+pub fn email_hash(email: &str) -> String {
+    blind_index("email", &email.trim().to_lowercase())
+}
+
+/// Identifies a message from an address, for duplicate detection.
+pub fn content_hash(email: &str, message: &str) -> String {
+    blind_index("content", &format!("{}\n{}", email.trim().to_lowercase(), message))
+}
+
+/// A client address, stored with consents and for throttling instead of the address itself.
+pub fn ip_hash(ip: IpAddr) -> String {
+    blind_index("ip", &ip.to_string())
+}
+
+/// Recomputes the stored hashes if they were made with another key than `ENCRYPTION_INDEX_KEY`,
+/// i.e. once when it is first set. Returns false if there was nothing to do.
+///
+/// Inquiries are hashed again from their decrypted values. Portal links and sessions, receipts and
+/// data requests only have the hash of an address, so theirs are mapped from what every address
+/// we still have hashed to under the encryption keys' earlier index keys. Hashes of addresses that
+/// are gone, and of client IP addresses, stay as they are and no longer match anything.
+pub async fn rehash(conn: &mut sqlx::SqliteConnection) -> Result<bool, sqlx::Error> {
+    use sqlx::Connection;
+    use std::collections::HashMap;
+
+    let keys = keys();
+    let hashed_with: Option<String> = sqlx::query_scalar("SELECT key_id FROM index_key WHERE id = 1")
+        .fetch_optional(&mut *conn)
+        .await?;
+    if hashed_with.as_deref() == Some(keys.index_key_id.as_str()) {
+        return Ok(false);
+    }
+
+    let mut tx = conn.begin().await?;
+    let mut addresses = Vec::new();
+
+    let rows: Vec<(i64, String, String)> = sqlx::query_as("SELECT id, email, message FROM emails WHERE email != ''")
+        .fetch_all(&mut *tx)
+        .await?;
+    for (id, email, message) in rows {
+        match (decrypt(Field::Email, &email), decrypt(Field::Message, &message)) {
+            (Ok(email), Ok(message)) => {
+                sqlx::query("UPDATE emails SET email_hash = $1, content_hash = $2 WHERE id = $3")
+                    .bind(email_hash(&email))
+                    .bind(content_hash(&email, &message))
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                addresses.push(email);
+            }
+            (Err(e), _) | (_, Err(e)) => log!("Inquiry {} could not be hashed again: {}", id, e),
+        }
+    }
+    addresses.extend(
+        sqlx::query_scalar::<_, String>("SELECT email FROM bookings WHERE email != ''")
+            .fetch_all(&mut *tx)
+            .await?,
+    );
+
+    let mut remapped = HashMap::new();
+    for email in &addresses {
+        let normalised = email.trim().to_lowercase();
+        for key in std::iter::once(&keys.current).chain(&keys.old) {
+            remapped.insert(blind_index_with(&key.legacy_index_key, "email", &normalised), email_hash(email));
+        }
+    }
+    for (table, column) in [
+        ("sent_receipts", "recipient"),
+        ("portal_links", "email_hash"),
+        ("portal_sessions", "email_hash"),
+        ("data_requests", "email_hash"),
+    ] {
+        let stored: Vec<String> = sqlx::query_scalar(&format!("SELECT DISTINCT {} FROM {}", column, table))
+            .fetch_all(&mut *tx)
+            .await?;
+        for (old, new) in stored.iter().filter_map(|old| remapped.get(old).map(|new| (old, new))) {
+            sqlx::query(&format!("UPDATE {} SET {} = $1 WHERE {} = $2", table, column, column))
+                .bind(new)
+                .bind(old)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    sqlx::query("INSERT INTO index_key (id, key_id) VALUES (1, $1) ON CONFLICT (id) DO UPDATE SET key_id = excluded.key_id")
+        .bind(&keys.index_key_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Encrypts inquiries that are stored in plain text (`only_plaintext`) or, for key rotation,
+/// every inquiry, message and job that isn't encrypted with the current key. Returns the number of
+/// inquiries updated. Rows that can't be decrypted are logged and left as they are.
+pub async fn reencrypt(conn: &mut sqlx::SqliteConnection, only_plaintext: bool) -> Result<u64, sqlx::Error> {
+    use sqlx::Connection;
+
+    let mut tx = conn.begin().await?;
+    let rows: Vec<(i64, String, String)> = sqlx::query_as("SELECT id, email, message FROM emails")
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let mut updated = 0;
+    for (id, email, message) in rows {
+        let is_plaintext = !email.starts_with(PREFIX) && !message.starts_with(PREFIX);
+        // Anonymised rows have nothing left to protect
+        let is_empty = email.is_empty() && message.is_empty();
+        let needs_work = if only_plaintext { is_plaintext } else { !(is_current(&email) && is_current(&message)) };
+        if is_empty || !needs_work {
+            continue;
+        }
+
+        let (email, message) = match (decrypt(Field::Email, &email), decrypt(Field::Message, &message)) {
+            (Ok(email), Ok(message)) => (email, message),
+            (Err(e), _) | (_, Err(e)) => {
+                log!("Inquiry {} could not be re-encrypted: {}", id, e);
+                continue;
+            }
+        };
+
+        sqlx::query("UPDATE emails SET email = $1, message = $2, email_hash = $3, content_hash = $4 WHERE id = $5")
+            .bind(encrypt(Field::Email, &email))
+            .bind(encrypt(Field::Message, &message))
+            .bind(email_hash(&email))
+            .bind(content_hash(&email, &message))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        updated += 1;
+    }
+
+    // These are only ever stored encrypted, they just need to move to the current key
+    if !only_plaintext {
+        for (table, column, field) in [
+            ("jobs", "payload", Field::JobPayload),
+            ("dead_jobs", "payload", Field::JobPayload),
+            ("messages", "body", Field::MessageBody),
+        ] {
+            let rows: Vec<(i64, String)> = sqlx::query_as(&format!("SELECT id, {} FROM {}", column, table))
+                .fetch_all(&mut *tx)
+                .await?;
+            for (id, value) in rows.into_iter().filter(|(_, value)| !value.is_empty() && !is_current(value)) {
+                match decrypt(field, &value) {
+                    Ok(value) => {
+                        sqlx::query(&format!("UPDATE {} SET {} = $1 WHERE id = $2", table, column))
+                            .bind(encrypt(field, &value))
+                            .bind(id)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                    Err(e) => log!("Row {} in {} could not be re-encrypted: {}", id, table, e),
+                }
+            }
+        }
+    }
+
+    tx.commit().await?;
+    Ok(updated)
+}
@@ -15,23 +15,81 @@ pub enum ContactFormError {
     EmailTooLong,
     #[error("MessageTooLong")]
     MessageTooLong,
+    #[error("MessageTooLarge")]
+    MessageTooLarge,
     #[error("MissingMessage")]
     MissingMessage,
     #[error("TermsNotAccepted")]
     TermsNotAccepted,
+    #[error("MissingServiceType")]
+    MissingServiceType,
+    #[error("InvalidBudget")]
+    InvalidBudget,
+    #[error("InvalidDeadline")]
+    InvalidDeadline,
+    #[error("CompanyTooLong")]
+    CompanyTooLong,
+    #[error("SpamSuspected")]
+    SpamSuspected,
+    #[error("SubmittedTooFast")]
+    SubmittedTooFast,
+    #[error("FormExpired")]
+    FormExpired,
+    #[error("RateLimited")]
+    RateLimited,
+    #[error("InvalidProofOfWork")]
+    InvalidProofOfWork,
+    #[error("DomainNotAllowed")]
+    DomainNotAllowed,
+    #[error("AttachmentTooLarge")]
+    AttachmentTooLarge,
+    #[error("AttachmentTypeNotAllowed")]
+    AttachmentTypeNotAllowed,
+    #[error("TooManyAttachments")]
+    TooManyAttachments,
+    #[error("AttachmentUploadFailed")]
+    AttachmentUploadFailed,
+    /// Several field errors at once. Displayed as a comma separated list of the variant names, which
+    /// `FromStr` parses back for the no-JS redirect.
+    #[error("{}", .0.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(","))]
+    Fields(Vec<ContactFormError>),
     #[error("DatabaseError")]
     DatabaseError(String),
 }
 
 impl ContactFormError {
+    /// The individual errors, so that every invalid field can be marked.
+    pub fn errors(&self) -> Vec<&ContactFormError> {
+        match self {
+            ContactFormError::Fields(errors) => errors.iter().flat_map(|e| e.errors()).collect(),
+            error => vec![error],
+        }
+    }
+
     pub fn get_user_message(&self) -> String {
         match self {
             ContactFormError::MissingEmail => "Ange en email-adress.".to_string(),
             ContactFormError::InvalidEmailFormat => "Ange en giltig email-adress.".to_string(),
             ContactFormError::EmailTooLong => "E-postadressen är för lång (max 254 tecken).".to_string(),
             ContactFormError::MessageTooLong => "Meddelandet är för långt (max 5000 tecken).".to_string(),
+            ContactFormError::MessageTooLarge => "Meddelandet tar för mycket plats (max 40 000 byte). Ta bort tecken med många accenter eller symboler.".to_string(),
             ContactFormError::MissingMessage => "Meddelandet får inte vara tomt.".to_string(),
             ContactFormError::TermsNotAccepted => "Du måste acceptera villkoren för att skicka meddelandet.".to_string(),
+            ContactFormError::MissingServiceType => "Välj vilken tjänst det gäller.".to_string(),
+            ContactFormError::InvalidBudget => "Välj en av budgetnivåerna.".to_string(),
+            ContactFormError::InvalidDeadline => "Ange ett datum som inte har passerat.".to_string(),
+            ContactFormError::CompanyTooLong => "Företagsnamnet är för långt (max 200 tecken).".to_string(),
+            ContactFormError::SpamSuspected => "Meddelandet kunde inte skickas. Mejla oss direkt om problemet kvarstår.".to_string(),
+            ContactFormError::SubmittedTooFast => "Formuläret skickades för snabbt. Vänta några sekunder och försök igen.".to_string(),
+            ContactFormError::FormExpired => "Formuläret har gått ut. Ladda om sidan och försök igen.".to_string(),
+            ContactFormError::RateLimited => "Du har skickat för många meddelanden på kort tid. Försök igen om en stund.".to_string(),
+            ContactFormError::InvalidProofOfWork => "Säkerhetskontrollen misslyckades. Ladda om sidan och försök igen.".to_string(),
+            ContactFormError::DomainNotAllowed => "Meddelandet kunde inte skickas från den här adressen. Använd en annan e-postadress.".to_string(),
+            ContactFormError::AttachmentTooLarge => "En bifogad fil är för stor (max 5 MB per fil).".to_string(),
+            ContactFormError::AttachmentTypeNotAllowed => "Endast bilder (PNG, JPEG, GIF, WebP) och PDF-filer kan bifogas.".to_string(),
+            ContactFormError::TooManyAttachments => "Du kan bifoga högst 3 filer.".to_string(),
+            ContactFormError::AttachmentUploadFailed => "Filerna kunde inte laddas upp. Försök igen.".to_string(),
+            ContactFormError::Fields(errors) => errors.iter().map(|e| e.get_user_message()).collect::<Vec<_>>().join(" "),
             ContactFormError::DatabaseError(s) => format!("Ett serverfel uppstod: {}", s),
         }
     }
@@ -67,15 +125,397 @@ impl FromStr for ContactFormError {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(',') {
+            return s
+                .split(',')
+                .map(ContactFormError::from_str)
+                .collect::<Result<Vec<_>, _>>()
+                .map(ContactFormError::Fields);
+        }
+
         match s {
             "MissingEmail" => Ok(ContactFormError::MissingEmail),
             "InvalidEmailFormat" => Ok(ContactFormError::InvalidEmailFormat),
             "EmailTooLong" => Ok(ContactFormError::EmailTooLong),
             "MessageTooLong" => Ok(ContactFormError::MessageTooLong),
+            "MessageTooLarge" => Ok(ContactFormError::MessageTooLarge),
             "MissingMessage" => Ok(ContactFormError::MissingMessage),
             "TermsNotAccepted" => Ok(ContactFormError::TermsNotAccepted),
+            "MissingServiceType" => Ok(ContactFormError::MissingServiceType),
+            "InvalidBudget" => Ok(ContactFormError::InvalidBudget),
+            "InvalidDeadline" => Ok(ContactFormError::InvalidDeadline),
+            "CompanyTooLong" => Ok(ContactFormError::CompanyTooLong),
+            "SpamSuspected" => Ok(ContactFormError::SpamSuspected),
+            "SubmittedTooFast" => Ok(ContactFormError::SubmittedTooFast),
+            "FormExpired" => Ok(ContactFormError::FormExpired),
+            "RateLimited" => Ok(ContactFormError::RateLimited),
+            "InvalidProofOfWork" => Ok(ContactFormError::InvalidProofOfWork),
+            "DomainNotAllowed" => Ok(ContactFormError::DomainNotAllowed),
+            "AttachmentTooLarge" => Ok(ContactFormError::AttachmentTooLarge),
+            "AttachmentTypeNotAllowed" => Ok(ContactFormError::AttachmentTypeNotAllowed),
+            "TooManyAttachments" => Ok(ContactFormError::TooManyAttachments),
+            "AttachmentUploadFailed" => Ok(ContactFormError::AttachmentUploadFailed),
             "DatabaseError" => Ok(ContactFormError::DatabaseError("Okänt databasfel".to_string())),
             _ => Err(format!("Okänt felvariantnamn kunde inte parsas: {}", s)),
         }
     }
 }
+
+#[derive(Error, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum AdminError {
+    #[error("NotAuthenticated")]
+    NotAuthenticated,
+    #[error("InvalidCredentials")]
+    InvalidCredentials,
+    #[error("RateLimited")]
+    RateLimited,
+    #[error("NotFound")]
+    NotFound,
+    #[error("InvalidStatusTransition")]
+    InvalidStatusTransition,
+    #[error("InvalidDomain")]
+    InvalidDomain,
+    #[error("InvalidDate")]
+    InvalidDate,
+    #[error("DateHasBookings")]
+    DateHasBookings,
+    #[error("EmptyReply")]
+    EmptyReply,
+    #[error("ReplyTooLong")]
+    ReplyTooLong,
+    #[error("ReplyTooLarge")]
+    ReplyTooLarge,
+    #[error("MailDisabled")]
+    MailDisabled,
+    #[error("DatabaseError")]
+    DatabaseError(String),
+}
+
+impl AdminError {
+    pub fn get_user_message(&self) -> String {
+        match self {
+            AdminError::NotAuthenticated => "Du måste logga in för att se den här sidan.".to_string(),
+            AdminError::InvalidCredentials => "Fel användarnamn eller lösenord.".to_string(),
+            AdminError::RateLimited => "För många inloggningsförsök. Vänta en stund och försök igen.".to_string(),
+            AdminError::NotFound => "Ärendet kunde inte hittas.".to_string(),
+            AdminError::InvalidStatusTransition => "Ärendet kan inte flyttas till den statusen.".to_string(),
+            AdminError::InvalidDomain => "Ange en giltig domän, t.ex. example.com.".to_string(),
+            AdminError::InvalidDate => "Ange ett giltigt datum.".to_string(),
+            AdminError::DateHasBookings => "Det finns bokningar den dagen. Kontakta kunderna och avboka dem innan dagen stängs.".to_string(),
+            AdminError::EmptyReply => "Skriv ett svar innan du skickar.".to_string(),
+            AdminError::ReplyTooLong => format!("Svaret får vara högst {} tecken.", crate::validation::MESSAGE_MAX_LENGTH),
+            AdminError::ReplyTooLarge => format!("Svaret får vara högst {} byte.", crate::validation::MESSAGE_MAX_BYTES),
+            AdminError::MailDisabled => "Svaret kan inte skickas eftersom utgående e-post inte är konfigurerad.".to_string(),
+            AdminError::DatabaseError(s) => format!("Ett serverfel uppstod: {}", s),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for AdminError {
+    fn from(e: sqlx::Error) -> Self {
+        AdminError::DatabaseError(e.to_string())
+    }
+}
+
+impl FromServerFnError for AdminError {
+    type Encoder = JsonEncoding;
+    fn from_server_fn_error(e: ServerFnErrorErr) -> Self {
+        match e {
+            ServerFnErrorErr::ServerError(s) => Self::DatabaseError(s),
+            _ => Self::DatabaseError(e.to_string()),
+        }
+    }
+}
+
+impl From<ServerFnError> for AdminError {
+    fn from(e: ServerFnError) -> Self {
+        match e {
+            ServerFnError::ServerError(s) => Self::DatabaseError(s),
+            _ => Self::DatabaseError(e.to_string()),
+        }
+    }
+}
+
+impl FromStr for AdminError {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "NotAuthenticated" => Ok(AdminError::NotAuthenticated),
+            "InvalidCredentials" => Ok(AdminError::InvalidCredentials),
+            "RateLimited" => Ok(AdminError::RateLimited),
+            "NotFound" => Ok(AdminError::NotFound),
+            "InvalidStatusTransition" => Ok(AdminError::InvalidStatusTransition),
+            "InvalidDomain" => Ok(AdminError::InvalidDomain),
+            "InvalidDate" => Ok(AdminError::InvalidDate),
+            "DateHasBookings" => Ok(AdminError::DateHasBookings),
+            "EmptyReply" => Ok(AdminError::EmptyReply),
+            "ReplyTooLong" => Ok(AdminError::ReplyTooLong),
+            "ReplyTooLarge" => Ok(AdminError::ReplyTooLarge),
+            "MailDisabled" => Ok(AdminError::MailDisabled),
+            "DatabaseError" => Ok(AdminError::DatabaseError("Okänt databasfel".to_string())),
+            _ => Err(format!("Okänt felvariantnamn kunde inte parsas: {}", s)),
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum BookingError {
+    #[error("MissingSlot")]
+    MissingSlot,
+    #[error("SlotUnavailable")]
+    SlotUnavailable,
+    #[error("SlotTaken")]
+    SlotTaken,
+    #[error("MissingEmail")]
+    MissingEmail,
+    #[error("InvalidEmailFormat")]
+    InvalidEmailFormat,
+    #[error("EmailTooLong")]
+    EmailTooLong,
+    #[error("MissingDescription")]
+    MissingDescription,
+    #[error("DescriptionTooLong")]
+    DescriptionTooLong,
+    #[error("DescriptionTooLarge")]
+    DescriptionTooLarge,
+    #[error("TermsNotAccepted")]
+    TermsNotAccepted,
+    #[error("SpamSuspected")]
+    SpamSuspected,
+    #[error("SubmittedTooFast")]
+    SubmittedTooFast,
+    #[error("FormExpired")]
+    FormExpired,
+    #[error("RateLimited")]
+    RateLimited,
+    #[error("TooManyPending")]
+    TooManyPending,
+    #[error("DomainNotAllowed")]
+    DomainNotAllowed,
+    #[error("InvalidLink")]
+    InvalidLink,
+    #[error("ConfirmationExpired")]
+    ConfirmationExpired,
+    #[error("TooLateToCancel")]
+    TooLateToCancel,
+    /// Several field errors at once, like `ContactFormError::Fields`.
+    #[error("{}", .0.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(","))]
+    Fields(Vec<BookingError>),
+    #[error("DatabaseError")]
+    DatabaseError(String),
+}
+
+impl BookingError {
+    pub fn errors(&self) -> Vec<&BookingError> {
+        match self {
+            BookingError::Fields(errors) => errors.iter().flat_map(|e| e.errors()).collect(),
+            error => vec![error],
+        }
+    }
+
+    pub fn get_user_message(&self) -> String {
+        match self {
+            BookingError::MissingSlot => "Välj en tid.".to_string(),
+            BookingError::SlotUnavailable => "Den valda tiden går inte att boka. Välj en annan tid.".to_string(),
+            BookingError::SlotTaken => "Tiden hann bokas av någon annan. Välj en annan tid.".to_string(),
+            BookingError::MissingEmail => "Ange en email-adress.".to_string(),
+            BookingError::InvalidEmailFormat => "Ange en giltig email-adress.".to_string(),
+            BookingError::EmailTooLong => "E-postadressen är för lång (max 254 tecken).".to_string(),
+            BookingError::MissingDescription => "Beskriv kort vad du behöver hjälp med.".to_string(),
+            BookingError::DescriptionTooLong => "Beskrivningen är för lång (max 5000 tecken).".to_string(),
+            BookingError::DescriptionTooLarge => "Beskrivningen tar för mycket plats (max 40 000 byte). Ta bort tecken med många accenter eller symboler.".to_string(),
+            BookingError::TermsNotAccepted => "Du måste acceptera villkoren för att boka.".to_string(),
+            BookingError::SpamSuspected => "Bokningen kunde inte skickas. Mejla oss direkt om problemet kvarstår.".to_string(),
+            BookingError::SubmittedTooFast => "Formuläret skickades för snabbt. Vänta några sekunder och försök igen.".to_string(),
+            BookingError::FormExpired => "Formuläret har gått ut. Ladda om sidan och försök igen.".to_string(),
+            BookingError::RateLimited => "Du har gjort för många bokningar på kort tid. Försök igen om en stund.".to_string(),
+            BookingError::TooManyPending => "Du har redan bokningar som väntar på bekräftelse. Bekräfta dem via länken i mejlet innan du bokar fler.".to_string(),
+            BookingError::DomainNotAllowed => "Bokningen kunde inte göras med den här adressen. Använd en annan e-postadress.".to_string(),
+            BookingError::InvalidLink => "Länken är ogiltig. Kontrollera att du har kopierat hela länken från mejlet.".to_string(),
+            BookingError::ConfirmationExpired => "Bokningen bekräftades inte i tid och tiden har släppts. Boka gärna en ny tid.".to_string(),
+            BookingError::TooLateToCancel => "Bokningen kan inte längre avbokas här. Mejla oss så hjälper vi dig.".to_string(),
+            BookingError::Fields(errors) => errors.iter().map(|e| e.get_user_message()).collect::<Vec<_>>().join(" "),
+            BookingError::DatabaseError(s) => format!("Ett serverfel uppstod: {}", s),
+        }
+    }
+}
+
+/// The booking form shares its email, description and spam checks with the contact form.
+impl From<ContactFormError> for BookingError {
+    fn from(e: ContactFormError) -> Self {
+        match e {
+            ContactFormError::MissingEmail => BookingError::MissingEmail,
+            ContactFormError::InvalidEmailFormat => BookingError::InvalidEmailFormat,
+            ContactFormError::EmailTooLong => BookingError::EmailTooLong,
+            ContactFormError::MissingMessage => BookingError::MissingDescription,
+            ContactFormError::MessageTooLong => BookingError::DescriptionTooLong,
+            ContactFormError::MessageTooLarge => BookingError::DescriptionTooLarge,
+            ContactFormError::TermsNotAccepted => BookingError::TermsNotAccepted,
+            ContactFormError::SpamSuspected => BookingError::SpamSuspected,
+            ContactFormError::SubmittedTooFast => BookingError::SubmittedTooFast,
+            ContactFormError::FormExpired => BookingError::FormExpired,
+            ContactFormError::RateLimited => BookingError::RateLimited,
+            ContactFormError::DomainNotAllowed => BookingError::DomainNotAllowed,
+            ContactFormError::Fields(errors) => BookingError::Fields(errors.into_iter().map(BookingError::from).collect()),
+            ContactFormError::DatabaseError(s) => BookingError::DatabaseError(s),
+            e => BookingError::DatabaseError(e.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for BookingError {
+    fn from(e: sqlx::Error) -> Self {
+        BookingError::DatabaseError(e.to_string())
+    }
+}
+
+impl FromServerFnError for BookingError {
+    type Encoder = JsonEncoding;
+    fn from_server_fn_error(e: ServerFnErrorErr) -> Self {
+        match e {
+            ServerFnErrorErr::ServerError(s) => Self::DatabaseError(s),
+            _ => Self::DatabaseError(e.to_string()),
+        }
+    }
+}
+
+impl From<ServerFnError> for BookingError {
+    fn from(e: ServerFnError) -> Self {
+        match e {
+            ServerFnError::ServerError(s) => Self::DatabaseError(s),
+            _ => Self::DatabaseError(e.to_string()),
+        }
+    }
+}
+
+impl FromStr for BookingError {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(',') {
+            return s
+                .split(',')
+                .map(BookingError::from_str)
+                .collect::<Result<Vec<_>, _>>()
+                .map(BookingError::Fields);
+        }
+
+        match s {
+            "MissingSlot" => Ok(BookingError::MissingSlot),
+            "SlotUnavailable" => Ok(BookingError::SlotUnavailable),
+            "SlotTaken" => Ok(BookingError::SlotTaken),
+            "MissingEmail" => Ok(BookingError::MissingEmail),
+            "InvalidEmailFormat" => Ok(BookingError::InvalidEmailFormat),
+            "EmailTooLong" => Ok(BookingError::EmailTooLong),
+            "MissingDescription" => Ok(BookingError::MissingDescription),
+            "DescriptionTooLong" => Ok(BookingError::DescriptionTooLong),
+            "DescriptionTooLarge" => Ok(BookingError::DescriptionTooLarge),
+            "TermsNotAccepted" => Ok(BookingError::TermsNotAccepted),
+            "SpamSuspected" => Ok(BookingError::SpamSuspected),
+            "SubmittedTooFast" => Ok(BookingError::SubmittedTooFast),
+            "FormExpired" => Ok(BookingError::FormExpired),
+            "RateLimited" => Ok(BookingError::RateLimited),
+            "TooManyPending" => Ok(BookingError::TooManyPending),
+            "DomainNotAllowed" => Ok(BookingError::DomainNotAllowed),
+            "InvalidLink" => Ok(BookingError::InvalidLink),
+            "ConfirmationExpired" => Ok(BookingError::ConfirmationExpired),
+            "TooLateToCancel" => Ok(BookingError::TooLateToCancel),
+            "DatabaseError" => Ok(BookingError::DatabaseError("Okänt databasfel".to_string())),
+            _ => Err(format!("Okänt felvariantnamn kunde inte parsas: {}", s)),
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PortalError {
+    #[error("MissingEmail")]
+    MissingEmail,
+    #[error("InvalidEmailFormat")]
+    InvalidEmailFormat,
+    #[error("RateLimited")]
+    RateLimited,
+    #[error("InvalidLink")]
+    InvalidLink,
+    #[error("LinkExpired")]
+    LinkExpired,
+    #[error("NotAuthenticated")]
+    NotAuthenticated,
+    #[error("ErasureNotConfirmed")]
+    ErasureNotConfirmed,
+    #[error("DatabaseError")]
+    DatabaseError(String),
+}
+
+impl PortalError {
+    pub fn get_user_message(&self) -> String {
+        match self {
+            PortalError::MissingEmail => "Ange en email-adress.".to_string(),
+            PortalError::InvalidEmailFormat => "Ange en giltig email-adress.".to_string(),
+            PortalError::RateLimited => "Du har begärt för många länkar på kort tid. Försök igen om en stund.".to_string(),
+            PortalError::InvalidLink => "Länken är ogiltig. Kontrollera att du har kopierat hela länken från mejlet.".to_string(),
+            PortalError::LinkExpired => "Länken har redan använts eller gått ut. Begär en ny länk nedan.".to_string(),
+            PortalError::NotAuthenticated => "Din inloggning har gått ut. Begär en ny länk för att se dina ärenden.".to_string(),
+            PortalError::ErasureNotConfirmed => "Kryssa i rutan för att bekräfta att allt ska raderas.".to_string(),
+            PortalError::DatabaseError(s) => format!("Ett serverfel uppstod: {}", s),
+        }
+    }
+}
+
+/// The portal asks for the address the same way as the contact form.
+impl From<ContactFormError> for PortalError {
+    fn from(e: ContactFormError) -> Self {
+        match e {
+            ContactFormError::MissingEmail => PortalError::MissingEmail,
+            ContactFormError::RateLimited => PortalError::RateLimited,
+            ContactFormError::DatabaseError(s) => PortalError::DatabaseError(s),
+            _ => PortalError::InvalidEmailFormat,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for PortalError {
+    fn from(e: sqlx::Error) -> Self {
+        PortalError::DatabaseError(e.to_string())
+    }
+}
+
+impl FromServerFnError for PortalError {
+    type Encoder = JsonEncoding;
+    fn from_server_fn_error(e: ServerFnErrorErr) -> Self {
+        match e {
+            ServerFnErrorErr::ServerError(s) => Self::DatabaseError(s),
+            _ => Self::DatabaseError(e.to_string()),
+        }
+    }
+}
+
+impl From<ServerFnError> for PortalError {
+    fn from(e: ServerFnError) -> Self {
+        match e {
+            ServerFnError::ServerError(s) => Self::DatabaseError(s),
+            _ => Self::DatabaseError(e.to_string()),
+        }
+    }
+}
+
+impl FromStr for PortalError {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "MissingEmail" => Ok(PortalError::MissingEmail),
+            "InvalidEmailFormat" => Ok(PortalError::InvalidEmailFormat),
+            "RateLimited" => Ok(PortalError::RateLimited),
+            "InvalidLink" => Ok(PortalError::InvalidLink),
+            "LinkExpired" => Ok(PortalError::LinkExpired),
+            "NotAuthenticated" => Ok(PortalError::NotAuthenticated),
+            "ErasureNotConfirmed" => Ok(PortalError::ErasureNotConfirmed),
+            "DatabaseError" => Ok(PortalError::DatabaseError("Okänt databasfel".to_string())),
+            _ => Err(format!("Okänt felvariantnamn kunde inte parsas: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Keeps a contact submission from being stored twice.
+//!
+//! Every rendered form carries a random key, which the island suffixes with the number of
+//! messages already sent from the page. A double-click, a retry after a slow response or a
+//! browser resending the no-JS POST therefore repeats the key, and is answered as if it had
+//! succeeded without storing anything. The key only counts together with the same address and
+//! message, a key sent again with other content is rejected. Submissions without a key (or with a new one) are also
+//! compared to recent ones: the same message from the same address within
+//! `DUPLICATE_WINDOW_MINUTES` (default 10) counts as a repeat.
+
+use rand::RngCore;
+
+/// Keys are generated by us, anything longer has been tampered with.
+const KEY_MAX_LENGTH: usize = 100;
+
+pub fn new_key() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// The key from the form, if it looks like one of ours.
+pub fn parse_key(key: Option<&str>) -> Option<&str> {
+    key.map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= KEY_MAX_LENGTH)
+        .filter(|key| key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+}
+
+fn window_minutes() -> i64 {
+    std::env::var("DUPLICATE_WINDOW_MINUTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|minutes: &i64| *minutes >= 0)
+        .unwrap_or(10)
+}
+
+/// The inquiry already stored with this key, and whether it has `content_hash` (see
+/// `crypto::content_hash`). A key repeated with other content is not a retry.
+pub async fn find_by_key(
+    conn: &mut sqlx::SqliteConnection,
+    key: &str,
+    content_hash: &str,
+) -> Result<Option<(i64, bool)>, sqlx::Error> {
+    let row: Option<(i64, Option<String>)> = sqlx::query_as("SELECT id, content_hash FROM emails WHERE idempotency_key = $1")
+        .bind(key)
+        .fetch_optional(conn)
+        .await?;
+    Ok(row.map(|(id, stored)| (id, stored.as_deref() == Some(content_hash))))
+}
+
+/// A recent inquiry with the same (normalised) address and message. The columns are encrypted,
+/// so this compares their keyed hash.
+pub async fn find_duplicate(
+    conn: &mut sqlx::SqliteConnection,
+    email: &str,
+    message: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT id FROM emails WHERE content_hash = $1 AND creation_date > datetime('now', $2) \
+         ORDER BY id LIMIT 1",
+    )
+    .bind(crate::crypto::content_hash(email, message))
+    .bind(format!("-{} minutes", window_minutes()))
+    .fetch_optional(conn)
+    .await
+}
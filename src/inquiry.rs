@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The lifecycle of an inquiry, stored in `emails.status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "ssr", sqlx(rename_all = "snake_case"))]
+pub enum InquiryStatus {
+    New,
+    InProgress,
+    WaitingOnCustomer,
+    Closed,
+}
+
+impl InquiryStatus {
+    pub const ALL: [InquiryStatus; 4] = [
+        InquiryStatus::New,
+        InquiryStatus::InProgress,
+        InquiryStatus::WaitingOnCustomer,
+        InquiryStatus::Closed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InquiryStatus::New => "new",
+            InquiryStatus::InProgress => "in_progress",
+            InquiryStatus::WaitingOnCustomer => "waiting_on_customer",
+            InquiryStatus::Closed => "closed",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            InquiryStatus::New => "Ny",
+            InquiryStatus::InProgress => "Pågående",
+            InquiryStatus::WaitingOnCustomer => "Väntar på kund",
+            InquiryStatus::Closed => "Avslutad",
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        *self != InquiryStatus::Closed
+    }
+
+    /// The states an inquiry may move to from this one. A closed inquiry can only be reopened.
+    pub fn next_states(&self) -> &'static [InquiryStatus] {
+        match self {
+            InquiryStatus::New => &[InquiryStatus::InProgress, InquiryStatus::WaitingOnCustomer, InquiryStatus::Closed],
+            InquiryStatus::InProgress => &[InquiryStatus::WaitingOnCustomer, InquiryStatus::Closed],
+            InquiryStatus::WaitingOnCustomer => &[InquiryStatus::InProgress, InquiryStatus::Closed],
+            InquiryStatus::Closed => &[InquiryStatus::InProgress],
+        }
+    }
+
+    pub fn can_transition_to(&self, next: InquiryStatus) -> bool {
+        self.next_states().contains(&next)
+    }
+}
+
+impl fmt::Display for InquiryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for InquiryStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        InquiryStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("Okänd status: {}", s))
+    }
+}
+
+/// The services offered on the home page, chosen in the contact form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "ssr", sqlx(rename_all = "snake_case"))]
+pub enum ServiceType {
+    SoftwareDevelopment,
+    PcHelp,
+}
+
+impl ServiceType {
+    pub const ALL: [ServiceType; 2] = [ServiceType::SoftwareDevelopment, ServiceType::PcHelp];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceType::SoftwareDevelopment => "software_development",
+            ServiceType::PcHelp => "pc_help",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ServiceType::SoftwareDevelopment => "Mjukvaruutveckling",
+            ServiceType::PcHelp => "Hjälp med persondatorer",
+        }
+    }
+}
+
+impl FromStr for ServiceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ServiceType::ALL
+            .into_iter()
+            .find(|service| service.as_str() == s)
+            .ok_or_else(|| format!("Okänd tjänst: {}", s))
+    }
+}
+
+/// The budget ranges a customer can pick for a quote, in SEK excluding VAT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+pub enum BudgetRange {
+    #[serde(rename = "under_10k")]
+    #[cfg_attr(feature = "ssr", sqlx(rename = "under_10k"))]
+    Under10k,
+    #[serde(rename = "10k_50k")]
+    #[cfg_attr(feature = "ssr", sqlx(rename = "10k_50k"))]
+    From10kTo50k,
+    #[serde(rename = "50k_150k")]
+    #[cfg_attr(feature = "ssr", sqlx(rename = "50k_150k"))]
+    From50kTo150k,
+    #[serde(rename = "over_150k")]
+    #[cfg_attr(feature = "ssr", sqlx(rename = "over_150k"))]
+    Over150k,
+}
+
+impl BudgetRange {
+    pub const ALL: [BudgetRange; 4] = [
+        BudgetRange::Under10k,
+        BudgetRange::From10kTo50k,
+        BudgetRange::From50kTo150k,
+        BudgetRange::Over150k,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetRange::Under10k => "under_10k",
+            BudgetRange::From10kTo50k => "10k_50k",
+            BudgetRange::From50kTo150k => "50k_150k",
+            BudgetRange::Over150k => "over_150k",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BudgetRange::Under10k => "Under 10 000 kr",
+            BudgetRange::From10kTo50k => "10 000–50 000 kr",
+            BudgetRange::From50kTo150k => "50 000–150 000 kr",
+            BudgetRange::Over150k => "Över 150 000 kr",
+        }
+    }
+}
+
+impl FromStr for BudgetRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BudgetRange::ALL
+            .into_iter()
+            .find(|budget| budget.as_str() == s)
+            .ok_or_else(|| format!("Okänd budget: {}", s))
+    }
+}
+
+/// One entry in the status history of an inquiry.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct StatusChange {
+    pub from_status: InquiryStatus,
+    pub to_status: InquiryStatus,
+    pub actor: String,
+    pub changed_at: String,
+}
+
+/// Who wrote a message in the thread of an inquiry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "ssr", sqlx(rename_all = "snake_case"))]
+pub enum MessageDirection {
+    /// From the customer.
+    Incoming,
+    /// A reply sent from the admin inbox.
+    Outgoing,
+}
+
+impl MessageDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageDirection::Incoming => "incoming",
+            MessageDirection::Outgoing => "outgoing",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MessageDirection::Incoming => "Från kunden",
+            MessageDirection::Outgoing => "Svar",
+        }
+    }
+}
+
+/// Whether a reply from the admin inbox has been mailed to the customer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "ssr", sqlx(rename_all = "snake_case"))]
+pub enum DeliveryStatus {
+    /// Waiting in the job queue, possibly for another attempt.
+    Queued,
+    Sent,
+    /// Gave up, the job is in `dead_jobs`.
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Queued => "queued",
+            DeliveryStatus::Sent => "sent",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Queued => "Skickas",
+            DeliveryStatus::Sent => "Skickat",
+            DeliveryStatus::Failed => "Kunde inte skickas",
+        }
+    }
+}
+
+/// A message in the thread of an inquiry, after the inquiry itself.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct ThreadMessage {
+    pub id: i64,
+    pub direction: MessageDirection,
+    pub body: String,
+    /// The admin who sent a reply.
+    pub author: Option<String>,
+    pub created_at: String,
+    /// Only set for replies.
+    pub delivery_status: Option<DeliveryStatus>,
+    /// The last error from mailing a reply.
+    pub delivery_error: Option<String>,
+}
@@ -0,0 +1,228 @@
+//! Persistent background jobs for side effects such as mail and webhooks.
+//!
+//! Jobs are rows in `jobs`, enqueued in the same transaction as whatever caused them, so a
+//! stored inquiry always has its follow-ups queued and nothing is queued for one that wasn't
+//! stored. `JOB_WORKERS` (default 2) workers started from `main.rs` run them. A failed job is
+//! retried after `JOB_RETRY_SECS` (default 30), doubling the wait every time, until it has been
+//! tried `JOB_MAX_ATTEMPTS` (default 6) times. Jobs that fail for good are moved to `dead_jobs`.
+//!
+//! The payload holds personal data, so it is encrypted like the inquiry itself.
+
+use crate::app::ssr::db;
+use crate::crypto::{self, Field};
+use crate::mail::OutgoingMail;
+use crate::webhooks::Delivery;
+use leptos::logging::log;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::Notify;
+
+/// Workers look for due retries this often even when nothing new is enqueued.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// A job claimed by a worker that has died is run again after this long.
+const LOCK_SECS: i64 = 300;
+const MAX_RETRY_SECS: u64 = 6 * 3600;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Job {
+    Mail(OutgoingMail),
+    Webhook(Delivery),
+}
+
+impl Job {
+    fn kind(&self) -> &'static str {
+        match self {
+            Job::Mail(_) => "mail",
+            Job::Webhook(_) => "webhook",
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum JobError {
+    /// Worth trying again later, e.g. the receiving server is down.
+    #[error("{0}")]
+    Retry(String),
+    /// Will fail the same way every time, e.g. mail is not configured.
+    #[error("{0}")]
+    Permanent(String),
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+static WAKE: OnceLock<Notify> = OnceLock::new();
+
+fn wake_signal() -> &'static Notify {
+    WAKE.get_or_init(Notify::new)
+}
+
+/// Lets a worker pick up newly enqueued jobs right away. Call it after the transaction that
+/// enqueued them has been committed.
+pub fn wake() {
+    wake_signal().notify_one();
+}
+
+/// Adds `job` to the queue and returns its id. `email_id` ties it to an inquiry, so that it is
+/// removed together with the inquiry.
+pub async fn enqueue(conn: &mut sqlx::SqliteConnection, email_id: Option<i64>, job: &Job) -> Result<i64, sqlx::Error> {
+    let payload = serde_json::to_string(job).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+
+    sqlx::query_scalar("INSERT INTO jobs (kind, email_id, payload, max_attempts) VALUES ($1, $2, $3, $4) RETURNING id")
+        .bind(job.kind())
+        .bind(email_id)
+        .bind(crypto::encrypt(Field::JobPayload, &payload))
+        .bind(env_or::<i64>("JOB_MAX_ATTEMPTS", 6).max(1))
+        .fetch_one(conn)
+        .await
+}
+
+async fn run(job: &Job, attempt: i64) -> Result<(), JobError> {
+    match job {
+        Job::Mail(mail) => crate::mail::send(mail.clone()).await.map_err(|e| match e {
+            crate::mail::MailError::Transport(_) => JobError::Retry(e.to_string()),
+            _ => JobError::Permanent(e.to_string()),
+        }),
+        Job::Webhook(delivery) => crate::webhooks::deliver(delivery, attempt).await,
+    }
+}
+
+struct Claimed {
+    id: i64,
+    payload: String,
+    attempts: i64,
+    max_attempts: i64,
+}
+
+/// Takes the next due job and locks it, in one statement so that two workers can't get the same.
+async fn claim(conn: &mut sqlx::SqliteConnection) -> Result<Option<Claimed>, sqlx::Error> {
+    let row: Option<(i64, String, i64, i64)> = sqlx::query_as(
+        "UPDATE jobs SET attempts = attempts + 1, locked_until = datetime('now', $1) \
+         WHERE id = (SELECT id FROM jobs WHERE run_at <= datetime('now') \
+                     AND (locked_until IS NULL OR locked_until <= datetime('now')) ORDER BY run_at, id LIMIT 1) \
+         RETURNING id, payload, attempts, max_attempts",
+    )
+    .bind(format!("+{} seconds", LOCK_SECS))
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(|(id, payload, attempts, max_attempts)| Claimed { id, payload, attempts, max_attempts }))
+}
+
+/// Removes a job that has succeeded, and marks the reply it mailed, if any, as sent.
+async fn finish(conn: &mut sqlx::SqliteConnection, id: i64) -> Result<(), sqlx::Error> {
+    use sqlx::Connection;
+
+    let mut tx = conn.begin().await?;
+    sqlx::query("UPDATE messages SET delivery_status = 'sent', delivery_error = NULL, job_id = NULL WHERE job_id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM jobs WHERE id = $1").bind(id).execute(&mut *tx).await?;
+    tx.commit().await
+}
+
+/// Moves a job that won't succeed to `dead_jobs`, and marks the reply it was mailing, if any,
+/// as failed.
+async fn bury(conn: &mut sqlx::SqliteConnection, id: i64, error: &str) -> Result<(), sqlx::Error> {
+    use sqlx::Connection;
+
+    let mut tx = conn.begin().await?;
+    sqlx::query("UPDATE messages SET delivery_status = 'failed', delivery_error = $2, job_id = NULL WHERE job_id = $1")
+        .bind(id)
+        .bind(error)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO dead_jobs (job_id, kind, email_id, payload, attempts, last_error, created_at) \
+         SELECT id, kind, email_id, payload, attempts, $2, created_at FROM jobs WHERE id = $1",
+    )
+    .bind(id)
+    .bind(error)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM jobs WHERE id = $1").bind(id).execute(&mut *tx).await?;
+    tx.commit().await
+}
+
+/// Runs the next due job. Returns false if there was none.
+async fn run_next(conn: &mut sqlx::SqliteConnection) -> Result<bool, sqlx::Error> {
+    let Some(claimed) = claim(&mut *conn).await? else {
+        return Ok(false);
+    };
+
+    let job = crypto::decrypt(Field::JobPayload, &claimed.payload)
+        .map_err(|e| JobError::Permanent(format!("could not decrypt payload: {}", e)))
+        .and_then(|payload| {
+            serde_json::from_str::<Job>(&payload).map_err(|e| JobError::Permanent(format!("invalid payload: {}", e)))
+        });
+    let result = match &job {
+        Ok(job) => run(job, claimed.attempts).await,
+        Err(e) => Err(JobError::Permanent(e.to_string())),
+    };
+    let kind = job.as_ref().map_or("unknown", Job::kind);
+
+    match result {
+        Ok(()) => finish(conn, claimed.id).await?,
+        Err(JobError::Retry(e)) if claimed.attempts < claimed.max_attempts => {
+            let base: u64 = env_or("JOB_RETRY_SECS", 30);
+            let wait = base.saturating_mul(1 << (claimed.attempts - 1).clamp(0, 20)).min(MAX_RETRY_SECS);
+            log!("Job {} ({}) failed (attempt {}), retrying in {} s: {}", claimed.id, kind, claimed.attempts, wait, e);
+            sqlx::query("UPDATE jobs SET run_at = datetime('now', $2), locked_until = NULL, last_error = $3 WHERE id = $1")
+                .bind(claimed.id)
+                .bind(format!("+{} seconds", wait))
+                .bind(&e)
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("UPDATE messages SET delivery_error = $2 WHERE job_id = $1")
+                .bind(claimed.id)
+                .bind(&e)
+                .execute(&mut *conn)
+                .await?;
+        }
+        Err(e) => {
+            log!("Job {} ({}) failed for good after {} attempts: {}", claimed.id, kind, claimed.attempts, e);
+            bury(conn, claimed.id, &e.to_string()).await?;
+        }
+    }
+    Ok(true)
+}
+
+/// Starts the workers. Jobs locked when the server stopped are released first, this is the only
+/// process working on the queue.
+pub async fn spawn_workers(conn: &mut sqlx::SqliteConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE jobs SET locked_until = NULL WHERE locked_until IS NOT NULL")
+        .execute(conn)
+        .await?;
+
+    let workers: usize = env_or("JOB_WORKERS", 2);
+    for worker in 0..workers.max(1) {
+        tokio::spawn(async move {
+            loop {
+                let ran = match db().await {
+                    Ok(mut conn) => run_next(&mut conn).await.unwrap_or_else(|e| {
+                        log!("Job worker {} could not run a job: {}", worker, e);
+                        false
+                    }),
+                    Err(e) => {
+                        log!("Job worker {} could not connect to the database: {}", worker, e);
+                        false
+                    }
+                };
+
+                // Keep going while there is work, otherwise wait for new jobs or due retries
+                if !ran {
+                    tokio::select! {
+                        _ = wake_signal().notified() => {}
+                        _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                    }
+                }
+            }
+        });
+    }
+    Ok(())
+}
@@ -1,6 +1,39 @@
+pub mod admin;
 pub mod app;
+#[cfg(feature = "ssr")]
+pub mod attachments;
+#[cfg(feature = "ssr")]
+pub mod blocklist;
+pub mod booking;
 pub mod contact;
+#[cfg(feature = "ssr")]
+pub mod crypto;
 pub mod error;
+#[cfg(feature = "ssr")]
+pub mod idempotency;
+pub mod inquiry;
+#[cfg(feature = "ssr")]
+pub mod jobs;
+#[cfg(feature = "ssr")]
+pub mod lmtp;
+#[cfg(feature = "ssr")]
+pub mod mail;
+pub mod portal;
+pub mod pow;
+#[cfg(feature = "ssr")]
+pub mod privacy;
+#[cfg(feature = "ssr")]
+pub mod rate_limit;
+#[cfg(feature = "ssr")]
+pub mod retention;
+#[cfg(feature = "ssr")]
+pub mod signing;
+#[cfg(feature = "ssr")]
+pub mod spam;
+pub mod terms;
+pub mod validation;
+#[cfg(feature = "ssr")]
+pub mod webhooks;
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
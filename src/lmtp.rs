@@ -0,0 +1,305 @@
+//! Mail from customers that continues an inquiry, delivered by the local mail server over LMTP
+//! (RFC 2033).
+//!
+//! The listener only runs when `LMTP_ADDR` is set, e.g. `127.0.0.1:2424`. LMTP has no
+//! authentication, so it must only be reachable by the mail server. A message is only added to
+//! an inquiry when it is sent to the address in the `Reply-To` of our replies,
+//! `<user>+<id>.<signature>@<domain>` built from `REPLY_ADDRESS`. The sender and subject are
+//! never trusted, since anyone can put an address in `From` and a reference number in the
+//! subject.
+//!
+//! The text part is added to the conversation and attachments of an allowed type are stored with
+//! the inquiry. Anything else is rejected, so the mail server bounces it.
+
+use crate::app::ssr::db;
+use crate::attachments::Attachment;
+use crate::crypto::{self, Field};
+use crate::inquiry::MessageDirection;
+use crate::validation::MAX_ATTACHMENTS;
+use leptos::logging::log;
+use mail_parser::MessageParser;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+
+const REPLY_TOKEN_PURPOSE: &str = "inquiry-reply";
+const HOSTNAME: &str = "tivanderit";
+/// Commands are short, RFC 5321 allows 512 bytes.
+const COMMAND_MAX_BYTES: u64 = 1024;
+const MAX_RECIPIENTS: usize = 10;
+const IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+fn max_message_bytes() -> usize {
+    std::env::var("LMTP_MAX_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(25 * 1024 * 1024)
+}
+
+/// `REPLY_ADDRESS` split into local part and domain, e.g. `svar@tivanderit.se`.
+fn reply_mailbox() -> Option<(String, String)> {
+    let address = std::env::var("REPLY_ADDRESS").ok()?;
+    let (user, domain) = address.trim().rsplit_once('@')?;
+    (!user.is_empty() && !domain.is_empty()).then(|| (user.to_string(), domain.to_string()))
+}
+
+/// The address that answers to our replies about inquiry `id` are sent to, if `REPLY_ADDRESS`
+/// is set.
+pub fn reply_address(id: i64) -> Option<String> {
+    let (user, domain) = reply_mailbox()?;
+    let signature = crate::signing::sign_short(REPLY_TOKEN_PURPOSE, &id.to_string());
+    Some(format!("{}+{}.{}@{}", user, id, signature, domain))
+}
+
+/// The inquiry a recipient address from `reply_address` belongs to.
+fn inquiry_for_recipient(recipient: &str) -> Option<i64> {
+    let (user, domain) = reply_mailbox()?;
+    let (local, recipient_domain) = recipient.rsplit_once('@')?;
+    if !recipient_domain.eq_ignore_ascii_case(&domain) {
+        return None;
+    }
+    let token = local.strip_prefix(&user)?.strip_prefix('+')?;
+    let (id, signature) = token.split_once('.')?;
+    crate::signing::verify_short(REPLY_TOKEN_PURPOSE, id, &signature.to_ascii_lowercase())
+        .then(|| id.parse().ok())
+        .flatten()
+}
+
+/// What is kept of an incoming mail.
+struct Incoming {
+    message_id: Option<String>,
+    text: String,
+    attachments: Vec<Attachment>,
+}
+
+fn parse(data: &[u8]) -> Option<Incoming> {
+    let message = MessageParser::default().parse(data)?;
+
+    let mut attachments = Vec::new();
+    for part in message.attachments() {
+        match crate::attachments::from_mail(part.attachment_name(), part.contents()) {
+            Some(attachment) if attachments.len() < MAX_ATTACHMENTS => attachments.push(attachment),
+            _ => log!("Left out an attachment of an incoming mail: not allowed or too many."),
+        }
+    }
+
+    Some(Incoming {
+        message_id: message.message_id().map(str::to_string),
+        text: message.body_text(0).map(|text| text.trim().to_string()).unwrap_or_default(),
+        attachments,
+    })
+}
+
+enum Delivery {
+    Stored(i64),
+    /// The mail server delivered the same message again, e.g. after a lost connection.
+    AlreadyStored(i64),
+    NoMatch,
+}
+
+/// The inquiry that `recipient`, one of our `Reply-To` addresses, belongs to.
+async fn find_inquiry(conn: &mut sqlx::SqliteConnection, recipient: &str) -> Result<Option<i64>, sqlx::Error> {
+    let Some(id) = inquiry_for_recipient(recipient) else {
+        return Ok(None);
+    };
+    sqlx::query_scalar("SELECT id FROM emails WHERE id = $1 AND anonymised_at IS NULL")
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+}
+
+async fn store(conn: &mut sqlx::SqliteConnection, recipient: &str, incoming: &Incoming) -> Result<Delivery, sqlx::Error> {
+    use sqlx::Connection;
+
+    let mut tx = conn.begin().await?;
+    let Some(id) = find_inquiry(&mut *tx, recipient).await? else {
+        return Ok(Delivery::NoMatch);
+    };
+
+    let inserted = sqlx::query(
+        "INSERT INTO messages (email_id, direction, body, mail_message_id) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(id)
+    .bind(MessageDirection::Incoming)
+    .bind(crypto::encrypt(Field::MessageBody, &incoming.text))
+    .bind(&incoming.message_id)
+    .execute(&mut *tx)
+    .await?;
+    if inserted.rows_affected() == 0 {
+        return Ok(Delivery::AlreadyStored(id));
+    }
+    crate::attachments::store(&mut tx, id, &incoming.attachments).await?;
+    tx.commit().await?;
+
+    Ok(Delivery::Stored(id))
+}
+
+async fn deliver(recipient: &str, incoming: &Incoming) -> Result<Delivery, String> {
+    let mut conn = db().await.map_err(|e| e.to_string())?;
+    store(&mut conn, recipient, incoming).await.map_err(|e| e.to_string())
+}
+
+async fn write(stream: &mut OwnedWriteHalf, reply: &str) -> std::io::Result<()> {
+    stream.write_all(reply.as_bytes()).await?;
+    stream.write_all(b"\r\n").await
+}
+
+/// Reads one line, `None` when the client has gone away or been idle for too long.
+async fn read_line(reader: &mut BufReader<OwnedReadHalf>, limit: u64) -> Option<Vec<u8>> {
+    let mut line = Vec::new();
+    match tokio::time::timeout(IDLE_TIMEOUT, (&mut *reader).take(limit).read_until(b'\n', &mut line)).await {
+        Ok(Ok(0)) | Ok(Err(_)) | Err(_) => None,
+        Ok(Ok(_)) => Some(line),
+    }
+}
+
+/// Reads the message after `DATA` up to the line with a single dot, undoing dot-stuffing.
+/// `None` if the connection ends first, `Some(None)` if the message is too large.
+async fn read_data(reader: &mut BufReader<OwnedReadHalf>) -> Option<Option<Vec<u8>>> {
+    let max_bytes = max_message_bytes();
+    let mut data = Vec::new();
+    let mut too_large = false;
+
+    loop {
+        let line = read_line(reader, 64 * 1024).await?;
+        if line == b".\r\n" || line == b".\n" {
+            return Some((!too_large).then_some(data));
+        }
+        let line = line.strip_prefix(b".").unwrap_or(&line[..]);
+        if data.len() + line.len() > max_bytes {
+            // Keep reading to the end so the session stays in sync
+            too_large = true;
+            data.clear();
+        } else if !too_large {
+            data.extend_from_slice(line);
+        }
+    }
+}
+
+/// The address in `MAIL FROM:<...>` or `RCPT TO:<...>`.
+fn path_argument(argument: &str) -> Option<String> {
+    let start = argument.find('<')?;
+    let end = argument[start..].find('>')? + start;
+    Some(argument[start + 1..end].trim().to_string())
+}
+
+async fn handle(stream: TcpStream) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    write(&mut writer, &format!("220 {} LMTP ready", HOSTNAME)).await?;
+
+    let mut greeted = false;
+    let mut sender: Option<String> = None;
+    let mut recipients: Vec<String> = Vec::new();
+
+    while let Some(line) = read_line(&mut reader, COMMAND_MAX_BYTES).await {
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end();
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+
+        match command.to_ascii_uppercase().as_str() {
+            "LHLO" => {
+                greeted = true;
+                sender = None;
+                recipients.clear();
+                write(&mut writer, &format!("250-{}", HOSTNAME)).await?;
+                write(&mut writer, "250-8BITMIME").await?;
+                write(&mut writer, "250-ENHANCEDSTATUSCODES").await?;
+                write(&mut writer, &format!("250 SIZE {}", max_message_bytes())).await?;
+            }
+            "MAIL" if !greeted => write(&mut writer, "503 5.5.1 Send LHLO first").await?,
+            "MAIL" => match path_argument(argument) {
+                Some(from) => {
+                    sender = Some(from);
+                    recipients.clear();
+                    write(&mut writer, "250 2.1.0 OK").await?;
+                }
+                None => write(&mut writer, "501 5.5.4 Syntax: MAIL FROM:<address>").await?,
+            },
+            "RCPT" if sender.is_none() => write(&mut writer, "503 5.5.1 Send MAIL first").await?,
+            "RCPT" if recipients.len() >= MAX_RECIPIENTS => write(&mut writer, "452 4.5.3 Too many recipients").await?,
+            "RCPT" => match path_argument(argument) {
+                Some(to) => {
+                    recipients.push(to);
+                    write(&mut writer, "250 2.1.5 OK").await?;
+                }
+                None => write(&mut writer, "501 5.5.4 Syntax: RCPT TO:<address>").await?,
+            },
+            "DATA" if recipients.is_empty() => write(&mut writer, "503 5.5.1 Send RCPT first").await?,
+            "DATA" => {
+                write(&mut writer, "354 Start mail input; end with <CRLF>.<CRLF>").await?;
+                let Some(data) = read_data(&mut reader).await else {
+                    return Ok(());
+                };
+
+                // LMTP answers once for every recipient
+                let incoming = data.as_deref().map(parse);
+                for recipient in &recipients {
+                    let reply = match &incoming {
+                        None => "552 5.3.4 Message too large".to_string(),
+                        Some(None) => "554 5.6.0 Message could not be parsed".to_string(),
+                        Some(Some(incoming)) => match deliver(recipient, incoming).await {
+                            Ok(Delivery::Stored(id)) => {
+                                log!("Mail to {} added to inquiry {}.", recipient, id);
+                                format!("250 2.0.0 Added to {}", crate::contact::reference_number(id))
+                            }
+                            Ok(Delivery::AlreadyStored(id)) => {
+                                format!("250 2.0.0 Already added to {}", crate::contact::reference_number(id))
+                            }
+                            Ok(Delivery::NoMatch) => {
+                                log!("Mail to {} rejected, it doesn't belong to an inquiry.", recipient);
+                                "550 5.1.1 No matching inquiry".to_string()
+                            }
+                            Err(e) => {
+                                log!("Mail to {} could not be stored: {}", recipient, e);
+                                "451 4.3.0 Temporary failure, try again later".to_string()
+                            }
+                        },
+                    };
+                    write(&mut writer, &reply).await?;
+                }
+                sender = None;
+                recipients.clear();
+            }
+            "RSET" => {
+                sender = None;
+                recipients.clear();
+                write(&mut writer, "250 2.0.0 OK").await?;
+            }
+            "NOOP" => write(&mut writer, "250 2.0.0 OK").await?,
+            "QUIT" => {
+                write(&mut writer, &format!("221 2.0.0 {} closing connection", HOSTNAME)).await?;
+                return Ok(());
+            }
+            _ => write(&mut writer, "500 5.5.2 Command not recognised").await?,
+        }
+    }
+    Ok(())
+}
+
+/// Starts the listener if `LMTP_ADDR` is set.
+pub async fn spawn_listener() -> std::io::Result<()> {
+    let Ok(addr) = std::env::var("LMTP_ADDR") else {
+        return Ok(());
+    };
+    let listener = TcpListener::bind(addr.trim()).await?;
+    log!("LMTP listening on {}", addr.trim());
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    tokio::spawn(async move {
+                        if let Err(e) = handle(stream).await {
+                            log!("LMTP session with {} ended with an error: {}", peer, e);
+                        }
+                    });
+                }
+                Err(e) => log!("Could not accept an LMTP connection: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
@@ -17,6 +17,35 @@ async fn main() {
 
     let m = Migrator::new(std::path::Path::new(&migrations_path)).await.expect("could't find the migrations");
     m.run(&mut conn).await.expect("Couldn't run migrations");
+
+    tivanderit::crypto::check_configuration();
+
+    // Hashes made before ENCRYPTION_INDEX_KEY was set
+    if tivanderit::crypto::rehash(&mut conn).await.expect("couldn't recompute stored hashes") {
+        log!("Recomputed stored hashes with the current ENCRYPTION_INDEX_KEY.");
+    }
+
+    // `tivanderit reencrypt` moves every inquiry to the current ENCRYPTION_KEY after a rotation
+    if std::env::args().nth(1).as_deref() == Some("reencrypt") {
+        let updated = tivanderit::crypto::reencrypt(&mut conn, false).await.expect("couldn't re-encrypt inquiries");
+        log!("Re-encrypted {} inquiries.", updated);
+        return;
+    }
+
+    // Inquiries stored before encryption was introduced
+    let encrypted = tivanderit::crypto::reencrypt(&mut conn, true).await.expect("couldn't encrypt stored inquiries");
+    if encrypted > 0 {
+        log!("Encrypted {} inquiries stored in plain text.", encrypted);
+    }
+
+    // Mail and webhooks queued by earlier requests, including those from before a restart
+    tivanderit::jobs::spawn_workers(&mut conn).await.expect("couldn't start the job workers");
+
+    // Customers' answers to our replies, from the local mail server
+    tivanderit::lmtp::spawn_listener().await.expect("couldn't start the LMTP listener");
+
+    // Purge or anonymise finished inquiries on a schedule
+    tivanderit::retention::spawn_scheduler(tivanderit::retention::RetentionConfig::from_env());
    
     let conf = get_configuration(None).unwrap();
     let addr = conf.leptos_options.site_addr;
@@ -26,17 +55,22 @@ async fn main() {
     let routes = generate_route_list(App);
 
     let app = Router::new()
+        .route("/admin/attachments/{id}", axum::routing::get(tivanderit::attachments::download))
+        .route("/portal/export", axum::routing::get(tivanderit::privacy::download))
         .leptos_routes(&leptos_options, routes, {
             let leptos_options = leptos_options.clone();
             move || shell(leptos_options.clone())
         })
         .fallback(leptos_axum::file_and_error_handler(shell))
         .with_state(leptos_options)
-        .layer(CompressionLayer::new());
+        .layer(CompressionLayer::new())
+        // Large enough for a contact form with attachments, the limits per file are checked while reading
+        .layer(tower_http::limit::RequestBodyLimitLayer::new(tivanderit::attachments::REQUEST_MAX_BYTES));
 
     log!("listening on http://{}", &addr);
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
+    // The peer address is needed for per-client rate limiting
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
         .await
         .unwrap();
 }
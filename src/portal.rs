@@ -0,0 +1,434 @@
+//! The customer portal at `/portal`, where the person who sent an inquiry can follow it.
+//!
+//! There are no passwords. The customer gives the address they wrote from and gets a signed,
+//! single-use link that is valid for `PORTAL_LINK_MINUTES` (default 15). Following it starts a
+//! session of `PORTAL_SESSION_MINUTES` (default 30) showing every inquiry from that address that
+//! hasn't been anonymised. Link and session tokens are only stored hashed, and both are tied to
+//! the keyed hash of the address rather than the address itself.
+//!
+//! Signed in customers can also download or erase their data, see `crate::privacy`.
+
+use leptos::prelude::*;
+use leptos_router::hooks::use_query_map;
+use crate::error::PortalError;
+use crate::inquiry::{InquiryStatus, MessageDirection, ServiceType, ThreadMessage};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// An inquiry as its sender sees it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortalInquiry {
+    pub id: i64,
+    pub creation_date: String,
+    pub status: InquiryStatus,
+    pub service_type: Option<ServiceType>,
+    pub message: String,
+    /// The conversation after the inquiry itself, oldest first.
+    pub messages: Vec<ThreadMessage>,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use crate::admin::ssr::{cookie_value, hash_token};
+    use crate::app::ssr::db;
+    use crate::error::PortalError;
+    use axum::http::HeaderMap;
+    use leptos_axum::extract;
+
+    pub const SESSION_COOKIE: &str = "portal_session";
+    /// Links mailed to one address per hour, so the form can't be used to flood someone's inbox.
+    pub const LINKS_PER_HOUR: i64 = 3;
+    pub const LINK_PURPOSE: &str = "portal-link";
+
+    fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+        std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+    }
+
+    pub fn link_minutes() -> i64 {
+        env_or("PORTAL_LINK_MINUTES", 15)
+    }
+
+    pub fn session_minutes() -> i64 {
+        env_or("PORTAL_SESSION_MINUTES", 30)
+    }
+
+    /// Returns the address hash of the signed in customer, or `NotAuthenticated`.
+    pub async fn require_customer() -> Result<String, PortalError> {
+        let headers: HeaderMap = extract().await.map_err(|_| PortalError::NotAuthenticated)?;
+        let token = cookie_value(&headers, SESSION_COOKIE).ok_or(PortalError::NotAuthenticated)?;
+        customer_for_token(&token).await
+    }
+
+    /// Returns the address hash for a session token, for handlers outside of server functions.
+    pub async fn customer_for_token(token: &str) -> Result<String, PortalError> {
+        let mut conn = db().await?;
+        let email_hash: Option<String> = sqlx::query_scalar(
+            "SELECT email_hash FROM portal_sessions WHERE token_hash = $1 AND expires_at > CURRENT_TIMESTAMP",
+        )
+        .bind(hash_token(token))
+        .fetch_optional(&mut conn)
+        .await?;
+
+        email_hash.ok_or(PortalError::NotAuthenticated)
+    }
+}
+
+/// `/portal`: the customer's inquiries when signed in, otherwise the form for requesting a link.
+#[component]
+pub fn PortalPage() -> impl IntoView {
+    let query_map = use_query_map();
+    let (sent, erased, error) = query_map.with(|params| {
+        let status = params.get("status").unwrap_or_default();
+        (
+            status == "sent",
+            status == "erased",
+            params.get("error").and_then(|error_str| PortalError::from_str(&error_str).ok()),
+        )
+    });
+
+    let inquiries = Resource::new(|| (), |_| list_portal_inquiries());
+
+    view! {
+        <div id="portal">
+            <h1>"Mina ärenden"</h1>
+            {error.map(|e| view! { <p class="error-message">{e.get_user_message()}</p> })}
+            {erased.then(|| view! {
+                <p class="success-message">"Dina uppgifter har raderats."</p>
+            })}
+            <Suspense fallback=|| view! { <p>"Laddar..."</p> }>
+                {move || Suspend::new(async move {
+                    match inquiries.await {
+                        Ok(inquiries) => view! { <PortalInquiries inquiries/> }.into_any(),
+                        Err(PortalError::NotAuthenticated) => view! { <PortalLinkRequest sent/> }.into_any(),
+                        Err(e) => view! { <p class="error-message">{e.get_user_message()}</p> }.into_any(),
+                    }
+                })}
+            </Suspense>
+        </div>
+    }
+}
+
+#[component]
+fn PortalLinkRequest(sent: bool) -> impl IntoView {
+    let request_action = ServerAction::<RequestPortalLink>::new();
+
+    view! {
+        <p>"Ange adressen du skrev till oss från så mejlar vi en länk där du kan se dina ärenden och våra svar."</p>
+        // The same answer whether or not we have anything from the address
+        {sent.then(|| view! {
+            <p class="success-message">"Om vi har ärenden från adressen har vi skickat en länk till den. Kolla din inkorg."</p>
+        })}
+        <ActionForm action=request_action>
+            <div class="form-field">
+                <label for="portal-email">"Email:"</label>
+                <input id="portal-email" type="email" name="email" autocomplete="email" required/>
+            </div>
+            <input type="submit" value="Skicka länk"/>
+        </ActionForm>
+    }
+}
+
+#[component]
+fn PortalInquiries(inquiries: Vec<PortalInquiry>) -> impl IntoView {
+    let logout_action = ServerAction::<PortalLogout>::new();
+    let erase_action = ServerAction::<EraseMyData>::new();
+
+    view! {
+        <ActionForm action=logout_action attr:class="portal-logout">
+            <input type="submit" value="Logga ut"/>
+        </ActionForm>
+        {inquiries.is_empty().then(|| view! { <p>"Vi har inga ärenden från din adress."</p> })}
+        {inquiries.into_iter().map(|inquiry| view! {
+            <section class="portal-inquiry">
+                <h2>{crate::contact::reference_number(inquiry.id)}</h2>
+                <dl>
+                    <dt>"Skickat"</dt>
+                    <dd>{inquiry.creation_date.clone()}</dd>
+                    <dt>"Status"</dt>
+                    <dd>{inquiry.status.label()}</dd>
+                    {inquiry.service_type.map(|service_type| view! {
+                        <dt>"Tjänst"</dt>
+                        <dd>{service_type.label()}</dd>
+                    })}
+                </dl>
+                <ol class="portal-thread">
+                    <li class="incoming">
+                        <p class="portal-thread-meta">{format!("{} – Du", inquiry.creation_date)}</p>
+                        <pre class="portal-message">{inquiry.message}</pre>
+                    </li>
+                    {inquiry.messages.into_iter().map(|message| {
+                        let from = match message.direction {
+                            MessageDirection::Incoming => "Du",
+                            MessageDirection::Outgoing => "Tivander IT",
+                        };
+                        view! {
+                            <li class=message.direction.as_str()>
+                                <p class="portal-thread-meta">{format!("{} – {}", message.created_at, from)}</p>
+                                <pre class="portal-message">{message.body}</pre>
+                            </li>
+                        }
+                    }).collect_view()}
+                </ol>
+            </section>
+        }).collect_view()}
+        <section class="portal-data">
+            <h2>"Dina uppgifter"</h2>
+            <p>"Här kan du ladda ner allt vi har sparat om din adress: ärenden, svar, bilagor, bokningar och samtycken."</p>
+            <p><a href="/portal/export" download>"Ladda ner mina uppgifter (JSON)"</a></p>
+            <p>"Du kan också radera allt direkt, i stället för att vänta tills vi gallrar avslutade ärenden. Pågående ärenden och bokade tider försvinner också."</p>
+            <ActionForm action=erase_action>
+                <div class="form-field">
+                    <input id="portal-erase-confirm" type="checkbox" name="confirm"/>
+                    <label for="portal-erase-confirm">"Jag förstår att mina uppgifter raderas och inte kan återställas."</label>
+                </div>
+                <input type="submit" class="danger" value="Radera mina uppgifter"/>
+            </ActionForm>
+        </section>
+    }
+}
+
+/// `/portal/login?token=…&sig=…`, linked from the mail. Like the booking links, following the
+/// link only shows a button, since mail scanners open links too and the link works once.
+#[component]
+pub fn PortalLoginPage() -> impl IntoView {
+    let query_map = use_query_map();
+    let (token, signature) = query_map.with(|params| {
+        (params.get("token").unwrap_or_default(), params.get("sig").unwrap_or_default())
+    });
+    let login_action = ServerAction::<PortalLogin>::new();
+
+    view! {
+        <div id="portal">
+            <h1>"Mina ärenden"</h1>
+            <ActionForm action=login_action>
+                <input type="hidden" name="token" value=token/>
+                <input type="hidden" name="sig" value=signature/>
+                <input type="submit" value="Visa mina ärenden"/>
+            </ActionForm>
+        </div>
+    }
+}
+
+/// Mails a sign-in link if there are inquiries or bookings from `email`. The answer is the same either way,
+/// so the form can't be used to find out who has written to us.
+#[server(RequestPortalLink, "/api")]
+pub async fn request_portal_link(email: String) -> Result<(), PortalError> {
+    use self::ssr::{link_minutes, LINKS_PER_HOUR, LINK_PURPOSE};
+    use crate::admin::ssr::{hash_token, new_token};
+    use crate::app::ssr::db;
+    use leptos_axum::redirect;
+    use leptos::logging::log;
+    use sqlx::Connection;
+
+    let email = match crate::validation::normalise_email(&email) {
+        Ok(email) => email,
+        Err(e) => {
+            let error = PortalError::from(e);
+            redirect(&format!("/portal?error={}", error));
+            return Err(error);
+        }
+    };
+    if !crate::rate_limit::check_contact_form().await {
+        let error = PortalError::RateLimited;
+        redirect(&format!("/portal?error={}", error));
+        return Err(error);
+    }
+
+    let email_hash = crate::crypto::email_hash(&email);
+    let mut conn = db().await?;
+    let mut tx = conn.begin().await?;
+
+    // Housekeeping, links are kept for a day to count towards the limit
+    sqlx::query("DELETE FROM portal_links WHERE created_at <= datetime('now', '-1 days')")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM portal_sessions WHERE expires_at <= CURRENT_TIMESTAMP")
+        .execute(&mut *tx)
+        .await?;
+
+    let inquiries: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM emails WHERE email_hash = $1 AND anonymised_at IS NULL")
+        .bind(&email_hash)
+        .fetch_one(&mut *tx)
+        .await?;
+    // People who have only booked can sign in too, for their data
+    let bookings = crate::privacy::booking_ids(&mut *tx, &email_hash).await?.len();
+    let recent_links: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM portal_links WHERE email_hash = $1 AND created_at > datetime('now', '-1 hours')",
+    )
+    .bind(&email_hash)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let has_data = inquiries > 0 || bookings > 0;
+    if has_data && recent_links < LINKS_PER_HOUR {
+        let token = new_token();
+        sqlx::query("INSERT INTO portal_links (token_hash, email_hash, expires_at) VALUES ($1, $2, datetime('now', $3))")
+            .bind(hash_token(&token))
+            .bind(&email_hash)
+            .bind(format!("+{} minutes", link_minutes()))
+            .execute(&mut *tx)
+            .await?;
+
+        let url = format!(
+            "{}/portal/login?token={}&sig={}",
+            crate::mail::site_url(),
+            token,
+            crate::signing::sign(LINK_PURPOSE, &token)
+        );
+        crate::mail::send_portal_link(&mut *tx, &email, &url, link_minutes()).await?;
+        log!("Portal link sent for an address with {} inquiries and {} bookings.", inquiries, bookings);
+    } else if has_data {
+        log!("Portal link not sent, too many requests for the address.");
+    }
+
+    tx.commit().await?;
+    crate::jobs::wake();
+
+    redirect("/portal?status=sent");
+    Ok(())
+}
+
+/// Uses up a sign-in link and starts a session.
+#[server(PortalLogin, "/api")]
+pub async fn portal_login(token: String, sig: String) -> Result<(), PortalError> {
+    use self::ssr::{session_minutes, LINK_PURPOSE, SESSION_COOKIE};
+    use crate::admin::ssr::{hash_token, new_token, set_cookie};
+    use crate::app::ssr::db;
+    use leptos_axum::redirect;
+    use sqlx::Connection;
+
+    if !crate::signing::verify(LINK_PURPOSE, &token, &sig) {
+        let error = PortalError::InvalidLink;
+        redirect(&format!("/portal?error={}", error));
+        return Err(error);
+    }
+
+    let mut conn = db().await?;
+    let mut tx = conn.begin().await?;
+
+    // Marking the link as used in the same statement that checks it, so it can't be used twice
+    let email_hash: Option<String> = sqlx::query_scalar(
+        "UPDATE portal_links SET used_at = CURRENT_TIMESTAMP \
+         WHERE token_hash = $1 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP RETURNING email_hash",
+    )
+    .bind(hash_token(&token))
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(email_hash) = email_hash else {
+        let error = PortalError::LinkExpired;
+        redirect(&format!("/portal?error={}", error));
+        return Err(error);
+    };
+
+    let session = new_token();
+    sqlx::query("INSERT INTO portal_sessions (token_hash, email_hash, expires_at) VALUES ($1, $2, datetime('now', $3))")
+        .bind(hash_token(&session))
+        .bind(&email_hash)
+        .bind(format!("+{} minutes", session_minutes()))
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    set_cookie(SESSION_COOKIE, &session, session_minutes() * 60);
+    redirect("/portal");
+    Ok(())
+}
+
+#[server(PortalLogout, "/api")]
+pub async fn portal_logout() -> Result<(), PortalError> {
+    use self::ssr::SESSION_COOKIE;
+    use crate::admin::ssr::{cookie_value, hash_token, set_cookie};
+    use crate::app::ssr::db;
+    use axum::http::HeaderMap;
+    use leptos_axum::{extract, redirect};
+
+    let headers: HeaderMap = extract().await.map_err(ServerFnError::from)?;
+    if let Some(token) = cookie_value(&headers, SESSION_COOKIE) {
+        let mut conn = db().await?;
+        sqlx::query("DELETE FROM portal_sessions WHERE token_hash = $1")
+            .bind(hash_token(&token))
+            .execute(&mut conn)
+            .await?;
+    }
+
+    set_cookie(SESSION_COOKIE, "", 0);
+    redirect("/portal");
+    Ok(())
+}
+
+/// The signed in customer's inquiries, newest first, with their conversations.
+#[server(ListPortalInquiries, "/api")]
+pub async fn list_portal_inquiries() -> Result<Vec<PortalInquiry>, PortalError> {
+    use self::ssr::require_customer;
+    use crate::app::ssr::db;
+    use crate::crypto::{decrypt_or_placeholder, Field};
+
+    let email_hash = require_customer().await?;
+    let mut conn = db().await?;
+
+    let rows: Vec<(i64, String, String, InquiryStatus, Option<ServiceType>)> = sqlx::query_as(
+        "SELECT id, message, creation_date, status, service_type FROM emails \
+         WHERE email_hash = $1 AND anonymised_at IS NULL ORDER BY creation_date DESC, id DESC",
+    )
+    .bind(&email_hash)
+    .fetch_all(&mut conn)
+    .await?;
+
+    let mut inquiries = Vec::with_capacity(rows.len());
+    for (id, message, creation_date, status, service_type) in rows {
+        let messages = sqlx::query_as::<_, ThreadMessage>(
+            "SELECT id, direction, body, author, created_at, delivery_status, delivery_error FROM messages \
+             WHERE email_id = $1 ORDER BY created_at, id",
+        )
+        .bind(id)
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        // Which admin answered, and how mailing it went, is internal
+        .map(|message| ThreadMessage {
+            body: decrypt_or_placeholder(Field::MessageBody, &message.body),
+            author: None,
+            delivery_status: None,
+            delivery_error: None,
+            ..message
+        })
+        .collect();
+
+        inquiries.push(PortalInquiry {
+            id,
+            creation_date,
+            status,
+            service_type,
+            message: decrypt_or_placeholder(Field::Message, &message),
+            messages,
+        });
+    }
+    Ok(inquiries)
+}
+
+/// Erases everything stored about the signed in customer's address and ends the session.
+#[server(EraseMyData, "/api")]
+pub async fn erase_my_data(confirm: Option<String>) -> Result<(), PortalError> {
+    use self::ssr::{require_customer, SESSION_COOKIE};
+    use crate::admin::ssr::set_cookie;
+    use crate::app::ssr::db;
+    use leptos_axum::redirect;
+    use leptos::logging::log;
+
+    let email_hash = require_customer().await?;
+    if confirm.is_none() {
+        let error = PortalError::ErasureNotConfirmed;
+        redirect(&format!("/portal?error={}", error));
+        return Err(error);
+    }
+
+    let mut conn = db().await?;
+    let ip = crate::rate_limit::client_ip().await;
+    let (inquiries, bookings) = crate::privacy::erase(&mut conn, &email_hash, ip).await?;
+    crate::jobs::wake();
+
+    log!("Data erased on request: {} inquiries, {} bookings.", inquiries, bookings);
+    set_cookie(SESSION_COOKIE, "", 0);
+    redirect("/portal?status=erased");
+    Ok(())
+}
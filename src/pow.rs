@@ -0,0 +1,138 @@
+//! A small hash-based proof of work, solved by the contact form island while the user types.
+//!
+//! The server hands out a signed challenge; the client searches for a number such that
+//! `sha256("<challenge>:<solution>")` starts with `difficulty` zero bits. Checking a solution
+//! takes one hash, finding it takes about `2^difficulty`, which is cheap for one visitor but adds
+//! up for a bot posting thousands of forms.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowChallenge {
+    pub challenge: String,
+    pub difficulty: u32,
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+pub fn meets_difficulty(challenge: &str, solution: u64, difficulty: u32) -> bool {
+    let hash = Sha256::digest(format!("{}:{}", challenge, solution).as_bytes());
+    leading_zero_bits(&hash) >= difficulty
+}
+
+/// Hands control back to the browser so that the page stays responsive while solving.
+async fn yield_to_browser() {
+    #[cfg(feature = "hydrate")]
+    {
+        let (tx, rx) = futures::channel::oneshot::channel();
+        leptos::prelude::set_timeout(
+            move || {
+                let _ = tx.send(());
+            },
+            std::time::Duration::ZERO,
+        );
+        let _ = rx.await;
+    }
+}
+
+pub async fn solve(challenge: &str, difficulty: u32) -> u64 {
+    const HASHES_PER_SLICE: u64 = 5_000;
+
+    let mut solution = 0;
+    loop {
+        for _ in 0..HASHES_PER_SLICE {
+            if meets_difficulty(challenge, solution, difficulty) {
+                return solution;
+            }
+            solution += 1;
+        }
+        yield_to_browser().await;
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{meets_difficulty, PowChallenge};
+    use crate::app::ssr::db;
+    use crate::error::ContactFormError;
+    use crate::signing;
+    use crate::spam::now_secs;
+    use rand::RngCore;
+
+    const CHALLENGE_PURPOSE: &str = "pow-challenge";
+
+    /// Solved challenges are remembered this long to reject replays, so they can't be valid longer.
+    const CHALLENGE_MAX_AGE_SECS: u64 = 3600;
+
+    /// `POW_DIFFICULTY` in bits, default 18 (a fraction of a second in a browser).
+    fn difficulty() -> u32 {
+        std::env::var("POW_DIFFICULTY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(18)
+    }
+
+    /// Issues a `<unix time>.<nonce>.<difficulty>.<signature>` challenge.
+    pub fn issue_challenge() -> PowChallenge {
+        let mut nonce = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let difficulty = difficulty();
+
+        let payload = format!("{}.{}.{}", now_secs(), hex::encode(nonce), difficulty);
+        let signature = signing::sign(CHALLENGE_PURPOSE, &payload);
+        PowChallenge {
+            challenge: format!("{}.{}", payload, signature),
+            difficulty,
+        }
+    }
+
+    /// Checks a solution and marks the challenge as used.
+    pub async fn verify(challenge: &str, solution: &str) -> Result<(), ContactFormError> {
+        let (payload, signature) = challenge.rsplit_once('.').ok_or(ContactFormError::InvalidProofOfWork)?;
+        if !signing::verify(CHALLENGE_PURPOSE, payload, signature) {
+            return Err(ContactFormError::InvalidProofOfWork);
+        }
+
+        // The difficulty is part of the signed payload, so it can't be lowered by the client
+        let mut parts = payload.split('.');
+        let issued_at: u64 = parts.next().and_then(|v| v.parse().ok()).ok_or(ContactFormError::InvalidProofOfWork)?;
+        let difficulty: u32 = parts.nth(1).and_then(|v| v.parse().ok()).ok_or(ContactFormError::InvalidProofOfWork)?;
+
+        if now_secs().saturating_sub(issued_at) > CHALLENGE_MAX_AGE_SECS {
+            return Err(ContactFormError::FormExpired);
+        }
+
+        let solution: u64 = solution.parse().map_err(|_| ContactFormError::InvalidProofOfWork)?;
+        if !meets_difficulty(challenge, solution, difficulty) {
+            return Err(ContactFormError::InvalidProofOfWork);
+        }
+
+        let mut conn = db().await?;
+        sqlx::query("DELETE FROM used_pow_challenges WHERE used_at < datetime('now', $1)")
+            .bind(format!("-{} seconds", CHALLENGE_MAX_AGE_SECS))
+            .execute(&mut conn)
+            .await?;
+
+        // The primary key makes a second use of the same challenge fail
+        sqlx::query("INSERT INTO used_pow_challenges (challenge) VALUES ($1)")
+            .bind(challenge)
+            .execute(&mut conn)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(e) if e.is_unique_violation() => ContactFormError::InvalidProofOfWork,
+                e => ContactFormError::from(e),
+            })?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+//! Self-service export and erasure of personal data, for the people who have sent inquiries or
+//! booked appointments.
+//!
+//! Both are reached from the customer portal, so the person has shown that they can read mail
+//! sent to the address. `GET /portal/export` downloads everything stored about the address as
+//! JSON, and an erasure deletes it all at once instead of waiting for retention. Every export and
+//! erasure is recorded in `data_requests`.
+
+use crate::admin::{Inquiry, INQUIRY_COLUMNS};
+use crate::app::ssr::db;
+use crate::booking::{Booking, BookingStatus};
+use crate::crypto::{self, decrypt_or_placeholder, Field};
+use crate::inquiry::{InquiryStatus, MessageDirection};
+use crate::jobs::Job;
+use crate::portal::ssr::{customer_for_token, SESSION_COOKIE};
+use crate::terms::Consent;
+use axum::{
+    extract::ConnectInfo,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use leptos::logging::log;
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr};
+
+#[derive(Serialize, Debug)]
+pub struct DataExport {
+    pub exported_at: String,
+    pub email: Option<String>,
+    pub inquiries: Vec<ExportedInquiry>,
+    pub bookings: Vec<ExportedBooking>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExportedInquiry {
+    pub reference: String,
+    #[serde(flatten)]
+    pub inquiry: Inquiry,
+    pub consent: Option<Consent>,
+    pub status_changes: Vec<ExportedStatusChange>,
+    pub messages: Vec<ExportedMessage>,
+    pub attachments: Vec<ExportedAttachment>,
+}
+
+/// Which admin made a change is left out, it is about us rather than the customer.
+#[derive(Serialize, Debug, sqlx::FromRow)]
+pub struct ExportedStatusChange {
+    pub from_status: InquiryStatus,
+    pub to_status: InquiryStatus,
+    pub changed_at: String,
+}
+
+#[derive(Serialize, Debug, sqlx::FromRow)]
+pub struct ExportedMessage {
+    pub direction: MessageDirection,
+    pub body: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExportedAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+    pub created_at: String,
+    /// The file itself, base64 encoded.
+    pub data: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExportedBooking {
+    #[serde(flatten)]
+    pub booking: Booking,
+    pub consent: Option<Consent>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum DataRequest {
+    Export,
+    Erasure,
+}
+
+impl DataRequest {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataRequest::Export => "export",
+            DataRequest::Erasure => "erasure",
+        }
+    }
+}
+
+/// Bookings keep the address in plain text, so they are matched by hashing each one. Finds the
+/// same bookings for an address however its case was written.
+pub(crate) async fn booking_ids(conn: &mut sqlx::SqliteConnection, email_hash: &str) -> Result<Vec<i64>, sqlx::Error> {
+    let rows: Vec<(i64, String)> = sqlx::query_as("SELECT id, email FROM bookings WHERE email != ''")
+        .fetch_all(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .filter(|(_, email)| crypto::email_hash(email) == email_hash)
+        .map(|(id, _)| id)
+        .collect())
+}
+
+/// Everything stored about the address with hash `email_hash`. Anonymised inquiries have no
+/// address left and aren't included.
+pub async fn export(conn: &mut sqlx::SqliteConnection, email_hash: &str) -> Result<DataExport, sqlx::Error> {
+    let rows: Vec<Inquiry> = sqlx::query_as::<_, Inquiry>(&format!(
+        "SELECT {} FROM emails WHERE email_hash = $1 ORDER BY creation_date, id",
+        INQUIRY_COLUMNS
+    ))
+    .bind(email_hash)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut inquiries = Vec::with_capacity(rows.len());
+    for inquiry in rows.into_iter().map(Inquiry::decrypted) {
+        let id = inquiry.id;
+        let status_changes = sqlx::query_as::<_, ExportedStatusChange>(
+            "SELECT from_status, to_status, changed_at FROM status_changes WHERE email_id = $1 ORDER BY changed_at, id",
+        )
+        .bind(id)
+        .fetch_all(&mut *conn)
+        .await?;
+        let messages = sqlx::query_as::<_, ExportedMessage>(
+            "SELECT direction, body, created_at FROM messages WHERE email_id = $1 ORDER BY created_at, id",
+        )
+        .bind(id)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|message| ExportedMessage { body: decrypt_or_placeholder(Field::MessageBody, &message.body), ..message })
+        .collect();
+        let attachments: Vec<(String, String, i64, String, Vec<u8>)> = sqlx::query_as(
+            "SELECT filename, content_type, size, created_at, data FROM attachments WHERE email_id = $1 ORDER BY id",
+        )
+        .bind(id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        inquiries.push(ExportedInquiry {
+            reference: crate::contact::reference_number(id),
+            consent: crate::terms::ssr::for_inquiry(&mut *conn, id).await?,
+            status_changes,
+            messages,
+            attachments: attachments
+                .into_iter()
+                .map(|(filename, content_type, size, created_at, data)| ExportedAttachment {
+                    filename,
+                    content_type,
+                    size,
+                    created_at,
+                    data: base64::engine::general_purpose::STANDARD.encode(data),
+                })
+                .collect(),
+            inquiry,
+        });
+    }
+
+    let mut bookings = Vec::new();
+    for id in booking_ids(&mut *conn, email_hash).await? {
+        let booking = sqlx::query_as::<_, Booking>(
+            "SELECT id, slot_start, email, description, status, created_at, confirmed_at, cancelled_at FROM bookings WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await?;
+        bookings.push(ExportedBooking { booking, consent: crate::terms::ssr::for_booking(&mut *conn, id).await? });
+    }
+
+    let email = inquiries
+        .iter()
+        .map(|exported| exported.inquiry.email.clone())
+        .chain(bookings.iter().map(|exported| exported.booking.email.clone()))
+        .next();
+
+    Ok(DataExport {
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        email,
+        inquiries,
+        bookings,
+    })
+}
+
+/// Deletes everything stored about the address with hash `email_hash`: inquiries with everything
+/// that belongs to them, bookings, queued mail to the address and portal sessions. Confirmed
+/// bookings that haven't happened yet are announced as cancelled, like when the customer cancels
+/// one, so the time isn't kept free for nobody. Returns the number of inquiries and bookings
+/// deleted; call `jobs::wake` afterwards.
+pub async fn erase(conn: &mut sqlx::SqliteConnection, email_hash: &str, ip: Option<IpAddr>) -> Result<(i64, i64), sqlx::Error> {
+    use sqlx::Connection;
+
+    let mut tx = conn.begin().await?;
+
+    let inquiry_ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM emails WHERE email_hash = $1")
+        .bind(email_hash)
+        .fetch_all(&mut *tx)
+        .await?;
+    for id in &inquiry_ids {
+        for query in [
+            "DELETE FROM status_changes WHERE email_id = $1",
+            "DELETE FROM sent_receipts WHERE email_id = $1",
+            "DELETE FROM attachments WHERE email_id = $1",
+            "DELETE FROM consents WHERE email_id = $1",
+            "DELETE FROM webhook_attempts WHERE email_id = $1",
+            "DELETE FROM jobs WHERE email_id = $1",
+            "DELETE FROM dead_jobs WHERE email_id = $1",
+            "DELETE FROM messages WHERE email_id = $1",
+            "DELETE FROM emails WHERE id = $1",
+        ] {
+            sqlx::query(query).bind(id).execute(&mut *tx).await?;
+        }
+    }
+
+    let booking_ids = booking_ids(&mut *tx, email_hash).await?;
+    for id in &booking_ids {
+        let (slot, email, status): (chrono::NaiveDateTime, String, BookingStatus) =
+            sqlx::query_as("SELECT slot_start, email, status FROM bookings WHERE id = $1")
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await?;
+        if status == BookingStatus::Confirmed && slot > crate::booking::ssr::now() {
+            crate::mail::notify_booking_cancelled(&mut tx, *id, &email, &crate::booking::slot_label(slot)).await?;
+        }
+        sqlx::query("DELETE FROM consents WHERE booking_id = $1").bind(id).execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM bookings WHERE id = $1").bind(id).execute(&mut *tx).await?;
+    }
+
+    // Mail that isn't tied to an inquiry, such as portal links, can only be found by its recipient
+    for table in ["jobs", "dead_jobs"] {
+        let rows: Vec<(i64, String)> = sqlx::query_as(&format!("SELECT id, payload FROM {} WHERE email_id IS NULL", table))
+            .fetch_all(&mut *tx)
+            .await?;
+        for (id, payload) in rows {
+            let to_address = crypto::decrypt(Field::JobPayload, &payload)
+                .ok()
+                .and_then(|payload| serde_json::from_str::<Job>(&payload).ok())
+                .is_some_and(|job| matches!(job, Job::Mail(mail) if crypto::email_hash(&mail.to) == email_hash));
+            if to_address {
+                sqlx::query(&format!("DELETE FROM {} WHERE id = $1", table)).bind(id).execute(&mut *tx).await?;
+            }
+        }
+    }
+
+    for query in [
+        "DELETE FROM sent_receipts WHERE recipient = $1",
+        "DELETE FROM portal_links WHERE email_hash = $1",
+        "DELETE FROM portal_sessions WHERE email_hash = $1",
+    ] {
+        sqlx::query(query).bind(email_hash).execute(&mut *tx).await?;
+    }
+
+    let counts = (inquiry_ids.len() as i64, booking_ids.len() as i64);
+    record(&mut *tx, DataRequest::Erasure, email_hash, counts, ip).await?;
+    tx.commit().await?;
+    Ok(counts)
+}
+
+/// Adds a request to the audit trail in `data_requests`.
+pub async fn record(
+    conn: &mut sqlx::SqliteConnection,
+    action: DataRequest,
+    email_hash: &str,
+    (inquiries, bookings): (i64, i64),
+    ip: Option<IpAddr>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO data_requests (action, email_hash, inquiries, bookings, ip_hash) VALUES ($1, $2, $3, $4, $5)")
+        .bind(action.as_str())
+        .bind(email_hash)
+        .bind(inquiries)
+        .bind(bookings)
+        .bind(ip.map(crate::terms::ssr::hash_ip))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// `GET /portal/export`, only for customers signed in to the portal. Served as a download.
+pub async fn download(ConnectInfo(peer): ConnectInfo<SocketAddr>, headers: HeaderMap) -> Response {
+    let Some(token) = crate::admin::ssr::cookie_value(&headers, SESSION_COOKIE) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let Ok(email_hash) = customer_for_token(&token).await else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let ip = crate::rate_limit::client_ip_from(peer, &headers);
+
+    let body: Result<String, String> = async {
+        let mut conn = db().await.map_err(|e| e.to_string())?;
+        let data = export(&mut conn, &email_hash).await.map_err(|e| e.to_string())?;
+        let counts = (data.inquiries.len() as i64, data.bookings.len() as i64);
+        let body = serde_json::to_string_pretty(&data).map_err(|e| e.to_string())?;
+        record(&mut conn, DataRequest::Export, &email_hash, counts, Some(ip))
+            .await
+            .map_err(|e| e.to_string())?;
+        log!("Data exported on request: {} inquiries, {} bookings.", counts.0, counts.1);
+        Ok(body)
+    }
+    .await;
+
+    match body {
+        Ok(body) => (
+            [
+                (header::CONTENT_TYPE, HeaderValue::from_static("application/json; charset=utf-8")),
+                (header::CONTENT_DISPOSITION, HeaderValue::from_static("attachment; filename=\"tivanderit-mina-uppgifter.json\"")),
+                (header::CACHE_CONTROL, HeaderValue::from_static("no-store")),
+            ],
+            body,
+        )
+            .into_response(),
+        Err(e) => {
+            log!("Could not export data: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Per-client rate limiting for the contact form and the admin login.
+//!
+//! Every client IP gets a token bucket holding `RATE_LIMIT_BURST` submissions (default 5) that
+//! refills completely over `RATE_LIMIT_WINDOW_SECS` (default 600). Admin logins have buckets of
+//! their own, `ADMIN_LOGIN_BURST` attempts (default 5) refilling over
+//! `ADMIN_LOGIN_WINDOW_SECS` (default 900). IPv6 clients are counted per /64, since that is
+//! what a single host or home network usually gets. The buckets live in memory, so they are reset
+//! when the server restarts.
+
+use axum::extract::ConnectInfo;
+use axum::http::HeaderMap;
+use leptos_axum::extract;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// At most this many clients have buckets of their own, further clients share one.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+/// Full buckets carry no information and are dropped this often.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+/// The bucket shared by clients without an address and by clients beyond `MAX_TRACKED_CLIENTS`.
+const SHARED: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Buckets {
+    by_client: HashMap<IpAddr, Bucket>,
+    swept: Instant,
+}
+
+pub struct RateLimiter {
+    burst: f64,
+    window: Duration,
+    buckets: Mutex<Buckets>,
+}
+
+/// What a client is counted as: IPv4 addresses as they are, IPv6 addresses by their /64.
+fn client_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => IpAddr::V6(Ipv6Addr::from(u128::from(v6) & !((1u128 << 64) - 1))),
+        },
+    }
+}
+
+impl RateLimiter {
+    pub fn new(burst: u32, window: Duration) -> Self {
+        Self {
+            burst: f64::from(burst.max(1)),
+            window,
+            buckets: Mutex::new(Buckets { by_client: HashMap::new(), swept: Instant::now() }),
+        }
+    }
+
+    /// Reads the burst and window from `<prefix>_BURST` and `<prefix>_WINDOW_SECS`.
+    pub fn from_env(prefix: &str, default_burst: u32, default_window_secs: u64) -> Self {
+        let burst = std::env::var(format!("{}_BURST", prefix))
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default_burst);
+        let window_secs = std::env::var(format!("{}_WINDOW_SECS", prefix))
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default_window_secs);
+        Self::new(burst, Duration::from_secs(window_secs))
+    }
+
+    fn refilled(&self, bucket: Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        let per_sec = self.burst / self.window.as_secs_f64().max(1.0);
+        (bucket.tokens + elapsed * per_sec).min(self.burst)
+    }
+
+    /// Takes one token from the client's bucket. Returns false if it is empty.
+    pub fn check(&self, client: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if now.duration_since(buckets.swept) >= SWEEP_INTERVAL {
+            buckets.by_client.retain(|_, bucket| self.refilled(*bucket, now) < self.burst);
+            buckets.swept = now;
+        }
+
+        let mut key = client_key(client);
+        if buckets.by_client.len() >= MAX_TRACKED_CLIENTS && !buckets.by_client.contains_key(&key) {
+            // Until the next sweep makes room, so that a flood of addresses can't grow the map
+            key = SHARED;
+        }
+
+        let bucket = buckets.by_client.entry(key).or_insert(Bucket { tokens: self.burst, updated: now });
+        let tokens = self.refilled(*bucket, now);
+        let allowed = tokens >= 1.0;
+        *bucket = Bucket {
+            tokens: if allowed { tokens - 1.0 } else { tokens },
+            updated: now,
+        };
+        allowed
+    }
+}
+
+fn contact_form_limiter() -> &'static RateLimiter {
+    static LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+    LIMITER.get_or_init(|| RateLimiter::from_env("RATE_LIMIT", 5, 600))
+}
+
+fn admin_login_limiter() -> &'static RateLimiter {
+    static LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+    LIMITER.get_or_init(|| RateLimiter::from_env("ADMIN_LOGIN", 5, 900))
+}
+
+/// Proxies whose `X-Forwarded-For` header is believed, from `TRUSTED_PROXIES` (comma separated
+/// addresses, e.g. `127.0.0.1,::1` when running behind a reverse proxy on the same host).
+fn trusted_proxies() -> &'static [IpAddr] {
+    static PROXIES: OnceLock<Vec<IpAddr>> = OnceLock::new();
+    PROXIES.get_or_init(|| {
+        std::env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|s| s.trim().parse().ok())
+            .collect()
+    })
+}
+
+/// Resolves the client address from the socket peer and, if the peer is a trusted proxy, from
+/// `X-Forwarded-For`. The header is walked from the right, since only the entries added by our
+/// own proxies can be trusted; the first address that isn't one of them is the client.
+pub fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpAddr]) -> IpAddr {
+    if !trusted.contains(&peer) {
+        return peer;
+    }
+
+    let forwarded: Vec<IpAddr> = headers
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|s| s.trim().parse().ok())
+        .collect();
+
+    forwarded
+        .iter()
+        .rev()
+        .find(|ip| !trusted.contains(ip))
+        .or(forwarded.first())
+        .copied()
+        .unwrap_or(peer)
+}
+
+/// The address of the client making the current request. Requires the server to be started
+/// with `into_make_service_with_connect_info::<SocketAddr>()`.
+pub async fn client_ip() -> Option<IpAddr> {
+    let ConnectInfo(peer): ConnectInfo<SocketAddr> = extract().await.ok()?;
+    let headers: HeaderMap = extract().await.ok()?;
+    Some(client_ip_from(peer, &headers))
+}
+
+/// The same as `client_ip`, for handlers outside of server functions.
+pub fn client_ip_from(peer: SocketAddr, headers: &HeaderMap) -> IpAddr {
+    resolve_client_ip(peer.ip(), headers, trusted_proxies())
+}
+
+/// Counts one contact form submission against the current client. Returns false if the client
+/// is over the limit.
+pub async fn check_contact_form() -> bool {
+    // Without an address all clients share one bucket, better than no limit at all
+    let client = client_ip().await.unwrap_or(SHARED);
+    contact_form_limiter().check(client)
+}
+
+/// Counts one admin login attempt against the current client, before the (deliberately slow)
+/// password check. Returns false if the client is over the limit.
+pub async fn check_admin_login() -> bool {
+    let client = client_ip().await.unwrap_or(SHARED);
+    admin_login_limiter().check(client)
+}
@@ -0,0 +1,194 @@
+//! Enforces the promise next to the terms checkbox: "Uppgifterna tas bort efter slutfört ärende".
+//!
+//! Closing an inquiry in the admin inbox sets `finished_at`. Once `RETENTION_GRACE_DAYS` have
+//! passed they are purged or anonymised by a task running inside the server, and every run is
+//! recorded in `retention_runs` for audit. Bookings are handled the same way, counting from the
+//! booked time.
+
+use crate::app::ssr::db;
+use leptos::logging::log;
+use leptos::server_fn::ServerFnError;
+use sqlx::Connection;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetentionMode {
+    /// Deletes the rows.
+    Purge,
+    /// Keeps the rows (and thereby the statistics), but blanks out the personal data.
+    Anonymise,
+}
+
+impl RetentionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RetentionMode::Purge => "purge",
+            RetentionMode::Anonymise => "anonymise",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RetentionConfig {
+    pub grace_days: i64,
+    pub mode: RetentionMode,
+    pub interval: Duration,
+}
+
+impl RetentionConfig {
+    /// Reads `RETENTION_GRACE_DAYS` (default 30), `RETENTION_MODE` (`purge` or `anonymise`,
+    /// default `purge`) and `RETENTION_INTERVAL_HOURS` (default 24).
+    ///
+    /// Panics on any other `RETENTION_MODE`, a misspelt `anonymise` must not end up deleting
+    /// the rows that were meant to be kept.
+    pub fn from_env() -> Self {
+        let grace_days = std::env::var("RETENTION_GRACE_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|days| *days >= 0)
+            .unwrap_or(30);
+        let mode = match std::env::var("RETENTION_MODE").as_deref() {
+            Err(_) | Ok("") | Ok("purge") => RetentionMode::Purge,
+            Ok("anonymise") => RetentionMode::Anonymise,
+            Ok(other) => panic!("RETENTION_MODE must be purge or anonymise, not {:?} - aborting startup", other),
+        };
+        let interval_hours = std::env::var("RETENTION_INTERVAL_HOURS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|hours| *hours > 0)
+            .unwrap_or(24);
+
+        RetentionConfig {
+            grace_days,
+            mode,
+            interval: Duration::from_secs(interval_hours * 3600),
+        }
+    }
+}
+
+async fn purge_expired(config: &RetentionConfig) -> Result<u64, ServerFnError> {
+    let mut conn = db().await?;
+    let cutoff = format!("-{} days", config.grace_days);
+
+    let mut tx = conn.begin().await?;
+
+    // Receipts contain the address and attachments are personal data, so they go together with the inquiry
+    for query in [
+        "DELETE FROM sent_receipts WHERE email_id IN \
+         (SELECT id FROM emails WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1) AND anonymised_at IS NULL)",
+        "DELETE FROM attachments WHERE email_id IN \
+         (SELECT id FROM emails WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1) AND anonymised_at IS NULL)",
+        // The consent itself stays as long as the inquiry does, but not what ties it to a person
+        "UPDATE consents SET ip_hash = NULL WHERE email_id IN \
+         (SELECT id FROM emails WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1) AND anonymised_at IS NULL)",
+        // Queued and failed jobs carry copies of the address and message
+        "DELETE FROM jobs WHERE email_id IN \
+         (SELECT id FROM emails WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1) AND anonymised_at IS NULL)",
+        "DELETE FROM dead_jobs WHERE email_id IN \
+         (SELECT id FROM emails WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1) AND anonymised_at IS NULL)",
+        // Failed mail that belongs to no inquiry, such as booking mail and portal links, is kept
+        // as long as a finished inquiry
+        "DELETE FROM dead_jobs WHERE email_id IS NULL AND failed_at <= datetime('now', $1)",
+    ] {
+        sqlx::query(query).bind(&cutoff).execute(&mut *tx).await?;
+    }
+
+    let bookings_queries = match config.mode {
+        RetentionMode::Purge => [
+            "DELETE FROM consents WHERE booking_id IN \
+             (SELECT id FROM bookings WHERE slot_start <= datetime('now', 'localtime', $1))",
+            "DELETE FROM bookings WHERE slot_start <= datetime('now', 'localtime', $1)",
+        ],
+        RetentionMode::Anonymise => [
+            "UPDATE consents SET ip_hash = NULL WHERE booking_id IN \
+             (SELECT id FROM bookings WHERE slot_start <= datetime('now', 'localtime', $1))",
+            "UPDATE bookings SET email = '', description = '' WHERE slot_start <= datetime('now', 'localtime', $1) AND email != ''",
+        ],
+    };
+    for query in bookings_queries {
+        sqlx::query(query).bind(&cutoff).execute(&mut *tx).await?;
+    }
+
+    let result = match config.mode {
+        RetentionMode::Purge => {
+            for query in [
+                "DELETE FROM status_changes WHERE email_id IN \
+                 (SELECT id FROM emails WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1))",
+                "DELETE FROM consents WHERE email_id IN \
+                 (SELECT id FROM emails WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1))",
+                "DELETE FROM webhook_attempts WHERE email_id IN \
+                 (SELECT id FROM emails WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1))",
+                "DELETE FROM messages WHERE email_id IN \
+                 (SELECT id FROM emails WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1))",
+            ] {
+                sqlx::query(query).bind(&cutoff).execute(&mut *tx).await?;
+            }
+
+            sqlx::query("DELETE FROM emails WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1)")
+                .bind(&cutoff)
+                .execute(&mut *tx)
+                .await?
+        }
+        RetentionMode::Anonymise => {
+            sqlx::query(
+                "UPDATE messages SET body = '' WHERE email_id IN \
+                 (SELECT id FROM emails WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1) AND anonymised_at IS NULL)",
+            )
+            .bind(&cutoff)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(
+                "UPDATE emails SET email = '', message = '', email_hash = NULL, content_hash = NULL, anonymised_at = CURRENT_TIMESTAMP \
+                 WHERE finished_at IS NOT NULL AND finished_at <= datetime('now', $1) AND anonymised_at IS NULL",
+            )
+            .bind(&cutoff)
+            .execute(&mut *tx)
+            .await?
+        }
+    };
+
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
+
+/// Runs one retention pass and records it in `retention_runs`.
+pub async fn run_once(config: &RetentionConfig) -> Result<u64, ServerFnError> {
+    let result = purge_expired(config).await;
+
+    let (affected_rows, error) = match &result {
+        Ok(rows) => (*rows as i64, None),
+        Err(e) => (0, Some(e.to_string())),
+    };
+    log!(
+        "Retention run ({}, {} days grace): {} inquiries affected{}",
+        config.mode.as_str(),
+        config.grace_days,
+        affected_rows,
+        error.as_deref().map(|e| format!(", error: {}", e)).unwrap_or_default()
+    );
+
+    let mut conn = db().await?;
+    sqlx::query("INSERT INTO retention_runs (mode, grace_days, affected_rows, error) VALUES ($1, $2, $3, $4)")
+        .bind(config.mode.as_str())
+        .bind(config.grace_days)
+        .bind(affected_rows)
+        .bind(error)
+        .execute(&mut conn)
+        .await?;
+
+    result
+}
+
+/// Starts the scheduled retention task. The first run happens right away.
+pub fn spawn_scheduler(config: RetentionConfig) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            // Errors are already logged and recorded, keep going with the next run
+            let _ = run_once(&config).await;
+        }
+    });
+}
@@ -0,0 +1,63 @@
+//! HMAC signatures for values that make a round trip through the browser or a mail client.
+
+use hmac::{Hmac, Mac};
+use leptos::logging::log;
+use rand::RngCore;
+use sha2::Sha256;
+use std::sync::OnceLock;
+
+type HmacSha256 = Hmac<Sha256>;
+
+static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// The key from `SIGNING_SECRET`. Without it a random key is used, which means that signed
+/// values (e.g. rendered forms) stop being valid when the server restarts.
+fn secret() -> &'static [u8] {
+    SECRET.get_or_init(|| match std::env::var("SIGNING_SECRET") {
+        Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+        _ => {
+            log!("SIGNING_SECRET is not set, using a random key. Signed values will not survive a restart.");
+            let mut secret = vec![0u8; 32];
+            rand::thread_rng().fill_bytes(&mut secret);
+            secret
+        }
+    })
+}
+
+/// `purpose` keeps a signature made for one thing from being accepted for another.
+fn mac(purpose: &str, value: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret()).expect("HMAC accepts keys of any length");
+    mac.update(purpose.as_bytes());
+    mac.update(b":");
+    mac.update(value.as_bytes());
+    mac
+}
+
+pub fn sign(purpose: &str, value: &str) -> String {
+    hex::encode(mac(purpose, value).finalize().into_bytes())
+}
+
+/// Checks `signature` in constant time.
+pub fn verify(purpose: &str, value: &str, signature: &str) -> bool {
+    hex::decode(signature)
+        .map(|signature| mac(purpose, value).verify_slice(&signature).is_ok())
+        .unwrap_or(false)
+}
+
+/// The first `SHORT_LENGTH` hex digits of a signature, for places with little room such as the
+/// local part of a mail address.
+pub const SHORT_LENGTH: usize = 20;
+
+pub fn sign_short(purpose: &str, value: &str) -> String {
+    let mut signature = sign(purpose, value);
+    signature.truncate(SHORT_LENGTH);
+    signature
+}
+
+/// Checks a signature from `sign_short` in constant time.
+pub fn verify_short(purpose: &str, value: &str, signature: &str) -> bool {
+    signature.len() == SHORT_LENGTH
+        && hex::decode(signature)
+            .map(|signature| mac(purpose, value).verify_truncated_left(&signature).is_ok())
+            .unwrap_or(false)
+}
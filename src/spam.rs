@@ -0,0 +1,100 @@
+//! Bot friction for the contact form, without any third party captcha.
+//!
+//! The form carries an invisible honeypot field that humans leave empty, and a signed timestamp
+//! from when it was rendered. Submissions that fill the honeypot or come back faster than a human
+//! could have typed the message are rejected. Both work without JS. Browsers that don't send a
+//! solved proof of work (see `crate::pow`) have to wait longer before submitting. A rendered form
+//! can only be submitted once, the islands fetch a new token after every submission.
+
+use crate::app::ssr::db;
+use crate::error::ContactFormError;
+use crate::signing;
+use rand::RngCore;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const FORM_TOKEN_PURPOSE: &str = "contact-form";
+
+/// How long a rendered form stays valid.
+const FORM_TOKEN_MAX_AGE_SECS: u64 = 24 * 3600;
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// `CONTACT_FORM_MIN_FILL_SECS` (default 3) with a solved proof of work, otherwise
+/// `CONTACT_FORM_NOJS_MIN_FILL_SECS` (default 10).
+fn min_fill_secs(solved_challenge: bool) -> u64 {
+    let (name, default) = if solved_challenge {
+        ("CONTACT_FORM_MIN_FILL_SECS", 3)
+    } else {
+        ("CONTACT_FORM_NOJS_MIN_FILL_SECS", 10)
+    };
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+/// A `<unix time>.<nonce>.<signature>` token rendered into the form. The nonce tells apart forms
+/// rendered in the same second.
+pub fn issue_form_token() -> String {
+    let mut nonce = [0u8; 8];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let payload = format!("{}.{}", now_secs(), hex::encode(nonce));
+    let signature = signing::sign(FORM_TOKEN_PURPOSE, &payload);
+    format!("{}.{}", payload, signature)
+}
+
+/// Returns the number of seconds since the form was rendered, if the token is genuine and
+/// not too old.
+pub fn form_age_secs(form_token: Option<&str>) -> Option<u64> {
+    let (payload, signature) = form_token?.rsplit_once('.')?;
+    if !signing::verify(FORM_TOKEN_PURPOSE, payload, signature) {
+        return None;
+    }
+
+    let issued_at = payload.split('.').next()?;
+    let age = now_secs().saturating_sub(issued_at.parse().ok()?);
+    (age <= FORM_TOKEN_MAX_AGE_SECS).then_some(age)
+}
+
+/// Marks the token as used, like a solved proof of work. Fails if it already has been.
+async fn use_form_token(form_token: &str) -> Result<(), ContactFormError> {
+    let mut conn = db().await?;
+    sqlx::query("DELETE FROM used_form_tokens WHERE used_at < datetime('now', $1)")
+        .bind(format!("-{} seconds", FORM_TOKEN_MAX_AGE_SECS))
+        .execute(&mut conn)
+        .await?;
+
+    // The primary key makes a second use of the same token fail
+    sqlx::query("INSERT INTO used_form_tokens (token) VALUES ($1)")
+        .bind(form_token)
+        .execute(&mut conn)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => ContactFormError::FormExpired,
+            e => ContactFormError::from(e),
+        })?;
+
+    Ok(())
+}
+
+pub async fn check_submission(
+    honeypot: Option<&str>,
+    form_token: Option<&str>,
+    solved_challenge: bool,
+) -> Result<(), ContactFormError> {
+    if honeypot.is_some_and(|value| !value.trim().is_empty()) {
+        return Err(ContactFormError::SpamSuspected);
+    }
+
+    // A forged token, one from before a key change and one that has already been sent look the
+    // same to the visitor, so they all get the friendly message
+    let age = form_age_secs(form_token).ok_or(ContactFormError::FormExpired)?;
+    if age < min_fill_secs(solved_challenge) {
+        return Err(ContactFormError::SubmittedTooFast);
+    }
+
+    use_form_token(form_token.unwrap_or_default()).await
+}
@@ -0,0 +1,164 @@
+//! The terms that people agree to with the checkboxes in the contact and booking forms.
+//!
+//! The checkbox label is the terms text. Changing a text means adding a new version here: the
+//! checkbox sends the version it was rendered with, and every submission stores a consent that
+//! points at the exact text in `terms_versions`.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TermsForm {
+    Contact,
+    Booking,
+}
+
+impl TermsForm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TermsForm::Contact => "contact",
+            TermsForm::Booking => "booking",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Terms {
+    pub form: TermsForm,
+    pub version: i64,
+    pub text: &'static str,
+}
+
+pub const CONTACT_TERMS: Terms = Terms {
+    form: TermsForm::Contact,
+    version: 1,
+    text: "Jag accepterar att informationen sparas. Uppgifterna tas bort efter slutfört ärende.",
+};
+
+pub const BOOKING_TERMS: Terms = Terms {
+    form: TermsForm::Booking,
+    version: 1,
+    text: "Jag accepterar att informationen sparas. Uppgifterna tas bort efter besöket.",
+};
+
+/// What a person agreed to, as shown to an admin.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct Consent {
+    pub version: i64,
+    pub text: String,
+    pub consented_at: String,
+    pub ip_hash: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{Consent, Terms};
+    use leptos::logging::log;
+    use std::net::IpAddr;
+
+    /// Keyed like the hashes in `crypto`, so the hash can be compared with the address of a later
+    /// request but not reversed by trying all addresses. That key must be configured, unlike
+    /// `SIGNING_SECRET` which falls back to a random one and would make the hashes meaningless
+    /// after a restart.
+    pub fn hash_ip(ip: IpAddr) -> String {
+        crate::crypto::ip_hash(ip)
+    }
+
+    /// The id of `terms` in `terms_versions`, adding it on first use.
+    async fn terms_version_id(conn: &mut sqlx::SqliteConnection, terms: &Terms) -> Result<i64, sqlx::Error> {
+        sqlx::query("INSERT OR IGNORE INTO terms_versions (form, version, text) VALUES ($1, $2, $3)")
+            .bind(terms.form.as_str())
+            .bind(terms.version)
+            .bind(terms.text)
+            .execute(&mut *conn)
+            .await?;
+
+        let (id, text): (i64, String) = sqlx::query_as("SELECT id, text FROM terms_versions WHERE form = $1 AND version = $2")
+            .bind(terms.form.as_str())
+            .bind(terms.version)
+            .fetch_one(&mut *conn)
+            .await?;
+        if text != terms.text {
+            log!(
+                "The {} terms text differs from the stored version {}, the version number must be raised when the text changes.",
+                terms.form.as_str(),
+                terms.version
+            );
+        }
+        Ok(id)
+    }
+
+    /// The version that the checkbox was rendered with. Checkboxes from before versioning send
+    /// "on", and an unknown version is treated the same way: the current terms apply.
+    pub async fn accepted_version_id(
+        conn: &mut sqlx::SqliteConnection,
+        current: &Terms,
+        checkbox_value: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let current_id = terms_version_id(conn, current).await?;
+        let Ok(version) = checkbox_value.parse::<i64>() else {
+            return Ok(current_id);
+        };
+
+        let id: Option<i64> = sqlx::query_scalar("SELECT id FROM terms_versions WHERE form = $1 AND version = $2")
+            .bind(current.form.as_str())
+            .bind(version)
+            .fetch_optional(&mut *conn)
+            .await?;
+        Ok(id.unwrap_or(current_id))
+    }
+
+    /// Stores the consent given with the inquiry `email_id`.
+    pub async fn record_for_inquiry(
+        conn: &mut sqlx::SqliteConnection,
+        email_id: i64,
+        checkbox_value: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<(), sqlx::Error> {
+        let terms_version_id = accepted_version_id(conn, &super::CONTACT_TERMS, checkbox_value).await?;
+        sqlx::query("INSERT INTO consents (terms_version_id, email_id, ip_hash) VALUES ($1, $2, $3)")
+            .bind(terms_version_id)
+            .bind(email_id)
+            .bind(ip.map(hash_ip))
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Stores the consent given with the booking `booking_id`.
+    pub async fn record_for_booking(
+        conn: &mut sqlx::SqliteConnection,
+        booking_id: i64,
+        checkbox_value: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<(), sqlx::Error> {
+        let terms_version_id = accepted_version_id(conn, &super::BOOKING_TERMS, checkbox_value).await?;
+        sqlx::query("INSERT INTO consents (terms_version_id, booking_id, ip_hash) VALUES ($1, $2, $3)")
+            .bind(terms_version_id)
+            .bind(booking_id)
+            .bind(ip.map(hash_ip))
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn for_inquiry(conn: &mut sqlx::SqliteConnection, email_id: i64) -> Result<Option<Consent>, sqlx::Error> {
+        sqlx::query_as::<_, Consent>(
+            "SELECT t.version, t.text, c.consented_at, c.ip_hash FROM consents c \
+             JOIN terms_versions t ON t.id = c.terms_version_id WHERE c.email_id = $1",
+        )
+        .bind(email_id)
+        .fetch_optional(conn)
+        .await
+    }
+
+    pub async fn for_booking(conn: &mut sqlx::SqliteConnection, booking_id: i64) -> Result<Option<Consent>, sqlx::Error> {
+        sqlx::query_as::<_, Consent>(
+            "SELECT t.version, t.text, c.consented_at, c.ip_hash FROM consents c \
+             JOIN terms_versions t ON t.id = c.terms_version_id WHERE c.booking_id = $1",
+        )
+        .bind(booking_id)
+        .fetch_optional(conn)
+        .await
+    }
+}
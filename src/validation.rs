@@ -0,0 +1,234 @@
+//! Validation rules for the contact form, shared by the island and `store_contact_form` so that
+//! both apply the same rules and show the same messages.
+
+use crate::error::ContactFormError;
+use crate::inquiry::{BudgetRange, ServiceType};
+use chrono::NaiveDate;
+use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// In characters, as the user counts them. The octet limits of RFC 5321 are checked on the local
+/// part and on the domain once it's converted to ASCII.
+pub const EMAIL_MAX_LENGTH: usize = 254;
+/// In graphemes, i.e. what a reader counts as one letter ("å" is one, however it's encoded).
+pub const MESSAGE_MAX_LENGTH: usize = 5000;
+/// A grapheme can be any number of bytes (a letter with endless combining marks), so the message
+/// is capped in bytes too, with an error of its own. Far above what 5000 letters of real text take.
+pub const MESSAGE_MAX_BYTES: usize = 40_000;
+
+pub const COMPANY_MAX_LENGTH: usize = 200;
+
+pub const MAX_ATTACHMENTS: usize = 3;
+pub const ATTACHMENT_MAX_BYTES: usize = 5 * 1024 * 1024;
+/// For the file picker. The server checks the file content, not the type claimed by the browser.
+pub const ATTACHMENT_ACCEPT: &str = "image/png,image/jpeg,image/gif,image/webp,application/pdf";
+
+pub fn email_length(email: &str) -> usize {
+    email.trim().chars().count()
+}
+
+pub fn message_length(message: &str) -> usize {
+    message.trim().graphemes(true).count()
+}
+
+/// Over `MESSAGE_MAX_BYTES` bytes. Check this before `message_too_long`, so that a huge message
+/// isn't split into graphemes.
+pub fn message_too_large(message: &str) -> bool {
+    message.trim().len() > MESSAGE_MAX_BYTES
+}
+
+/// Over `MESSAGE_MAX_LENGTH` graphemes.
+pub fn message_too_long(message: &str) -> bool {
+    message_length(message) > MESSAGE_MAX_LENGTH
+}
+
+/// `atext` from RFC 5322, plus any non-ASCII character as allowed by RFC 6532 (EAI).
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c) || (!c.is_ascii() && !c.is_control())
+}
+
+fn is_qtext(c: char) -> bool {
+    c == ' ' || c.is_ascii_graphic() || (!c.is_ascii() && !c.is_control())
+}
+
+/// A `dot-atom` or `quoted-string` local part of at most 64 octets (RFC 5321).
+fn is_valid_local_part(local: &str) -> bool {
+    if local.is_empty() || local.len() > 64 {
+        return false;
+    }
+
+    if let Some(quoted) = local.strip_prefix('"').and_then(|l| l.strip_suffix('"')) {
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            let valid = match c {
+                '\\' => chars.next().is_some_and(is_qtext),
+                '"' => false,
+                c => is_qtext(c),
+            };
+            if !valid {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    local.split('.').all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+}
+
+/// Converts internationalised domains to their ASCII (punycode) form, lowercased, and checks that
+/// the result is a host name with a top level domain. Domain literals (`[192.0.2.1]`) are
+/// technically valid but not accepted.
+pub fn normalise_domain(domain: &str) -> Option<String> {
+    let ascii = idna::domain_to_ascii(domain).ok()?;
+    let labels: Vec<&str> = ascii.split('.').collect();
+
+    let valid_labels = labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    let has_tld = labels.len() >= 2 && !labels.last()?.chars().all(|c| c.is_ascii_digit());
+
+    (ascii.len() <= 253 && valid_labels && has_tld).then_some(ascii)
+}
+
+/// Parses an RFC 5322 `addr-spec` and returns it in canonical form: the domain as lowercase ASCII,
+/// the local part as written (it is case sensitive by the standard).
+pub fn normalise_email(email: &str) -> Result<String, ContactFormError> {
+    let email = email.trim();
+
+    if email.is_empty() {
+        return Err(ContactFormError::MissingEmail);
+    }
+    if email_length(email) > EMAIL_MAX_LENGTH {
+        return Err(ContactFormError::EmailTooLong);
+    }
+
+    // A quoted local part may contain '@', the domain never does
+    let (local, domain) = email.rsplit_once('@').ok_or(ContactFormError::InvalidEmailFormat)?;
+    if !is_valid_local_part(local) {
+        return Err(ContactFormError::InvalidEmailFormat);
+    }
+    let domain = normalise_domain(domain).ok_or(ContactFormError::InvalidEmailFormat)?;
+
+    Ok(format!("{}@{}", local, domain))
+}
+
+pub fn validate_email(email: &str) -> Result<(), ContactFormError> {
+    normalise_email(email).map(|_| ())
+}
+
+pub fn validate_message(message: &str) -> Result<(), ContactFormError> {
+    let message = message.trim();
+
+    if message.is_empty() {
+        return Err(ContactFormError::MissingMessage);
+    }
+    if message_too_large(message) {
+        return Err(ContactFormError::MessageTooLarge);
+    }
+    if message_too_long(message) {
+        return Err(ContactFormError::MessageTooLong);
+    }
+    Ok(())
+}
+
+pub fn validate_terms(accepted: bool) -> Result<(), ContactFormError> {
+    if !accepted {
+        return Err(ContactFormError::TermsNotAccepted);
+    }
+    Ok(())
+}
+
+pub fn parse_service_type(service_type: &str) -> Result<ServiceType, ContactFormError> {
+    match service_type.trim() {
+        "" => Err(ContactFormError::MissingServiceType),
+        s => ServiceType::from_str(s).map_err(|_| ContactFormError::MissingServiceType),
+    }
+}
+
+/// The budget is optional, an empty value means that none was given.
+pub fn parse_budget(budget: &str) -> Result<Option<BudgetRange>, ContactFormError> {
+    match budget.trim() {
+        "" => Ok(None),
+        s => BudgetRange::from_str(s).map(Some).map_err(|_| ContactFormError::InvalidBudget),
+    }
+}
+
+/// The deadline is optional, but can't be in the past. `today` is passed in so that the island
+/// and the server each use their own clock.
+pub fn parse_deadline(deadline: &str, today: NaiveDate) -> Result<Option<NaiveDate>, ContactFormError> {
+    match deadline.trim() {
+        "" => Ok(None),
+        s => match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            Ok(date) if date >= today => Ok(Some(date)),
+            _ => Err(ContactFormError::InvalidDeadline),
+        },
+    }
+}
+
+pub fn parse_company(company: &str) -> Result<Option<String>, ContactFormError> {
+    let company = company.trim();
+    if company.chars().count() > COMPANY_MAX_LENGTH {
+        return Err(ContactFormError::CompanyTooLong);
+    }
+    Ok((!company.is_empty()).then(|| company.to_string()))
+}
+
+/// Today's date in the local time zone, as used for the deadline.
+pub fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+/// The contact form as entered, before validation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ContactFormFields<'a> {
+    pub message: &'a str,
+    pub email: &'a str,
+    pub terms_accepted: bool,
+    pub service_type: &'a str,
+    pub budget: &'a str,
+    pub deadline: &'a str,
+    pub company: &'a str,
+}
+
+/// The structured part of a valid quote request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuoteDetails {
+    pub service_type: ServiceType,
+    pub budget: Option<BudgetRange>,
+    pub deadline: Option<NaiveDate>,
+    pub company: Option<String>,
+}
+
+/// Validates the whole form. Every invalid field is reported, a single error is returned as is
+/// and several as `ContactFormError::Fields`.
+pub fn validate_contact_form(fields: &ContactFormFields, today: NaiveDate) -> Result<QuoteDetails, ContactFormError> {
+    let service_type = parse_service_type(fields.service_type);
+    let budget = parse_budget(fields.budget);
+    let deadline = parse_deadline(fields.deadline, today);
+    let company = parse_company(fields.company);
+
+    let mut errors: Vec<ContactFormError> = [
+        validate_email(fields.email).err(),
+        validate_message(fields.message).err(),
+        service_type.as_ref().err().cloned(),
+        budget.as_ref().err().cloned(),
+        deadline.as_ref().err().cloned(),
+        company.as_ref().err().cloned(),
+        validate_terms(fields.terms_accepted).err(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    match (service_type, budget, deadline, company) {
+        (Ok(service_type), Ok(budget), Ok(deadline), Ok(company)) if errors.is_empty() => {
+            Ok(QuoteDetails { service_type, budget, deadline, company })
+        }
+        _ if errors.len() == 1 => Err(errors.remove(0)),
+        _ => Err(ContactFormError::Fields(errors)),
+    }
+}
@@ -0,0 +1,196 @@
+//! Outgoing webhooks, so that new inquiries reach other tools (a ticket system, a chat bot)
+//! without polling the database.
+//!
+//! Every URL in `WEBHOOK_URLS` gets a JSON `POST` per event. The body is signed with
+//! `WEBHOOK_SECRET`, the `X-Webhook-Signature` header is `t=<unix time>,v1=<hex>` where `v1` is
+//! HMAC-SHA256 of `<unix time>.<body>`. Receivers should check it and reject old timestamps.
+//! `X-Webhook-Id` is the same for every attempt of a delivery, so retries can be recognised.
+//!
+//! Deliveries are jobs (see `crate::jobs`), so anything but a 2xx response is retried with
+//! backoff and survives a restart. Every attempt is recorded in `webhook_attempts`.
+
+use crate::app::ssr::db;
+use crate::inquiry::{BudgetRange, ServiceType};
+use crate::jobs::{Job, JobError};
+use chrono::NaiveDate;
+use hmac::{Hmac, Mac};
+use leptos::logging::log;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+const ID_HEADER: &str = "X-Webhook-Id";
+const EVENT_HEADER: &str = "X-Webhook-Event";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The body of an `inquiry.created` event.
+#[derive(Serialize, Debug)]
+pub struct InquiryCreated<'a> {
+    pub id: i64,
+    pub reference: String,
+    pub created_at: &'a str,
+    pub email: &'a str,
+    pub message: &'a str,
+    pub service_type: ServiceType,
+    pub budget: Option<BudgetRange>,
+    pub deadline: Option<NaiveDate>,
+    pub company: Option<&'a str>,
+}
+
+/// One event for one URL, as queued in `jobs`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Delivery {
+    /// Sent as `X-Webhook-Id`, the same for every attempt.
+    pub id: String,
+    pub email_id: i64,
+    pub event: String,
+    pub url: String,
+    pub body: String,
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T: Serialize> {
+    event: &'a str,
+    data: T,
+}
+
+struct Config {
+    urls: Vec<String>,
+    secret: Vec<u8>,
+    client: reqwest::Client,
+}
+
+static CONFIG: OnceLock<Option<Config>> = OnceLock::new();
+
+/// Webhooks are disabled without `WEBHOOK_URLS`, and without `WEBHOOK_SECRET` since unsigned
+/// deliveries can't be trusted by the receiver.
+fn config() -> Option<&'static Config> {
+    CONFIG
+        .get_or_init(|| {
+            let urls: Vec<String> = std::env::var("WEBHOOK_URLS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(str::to_string)
+                .collect();
+            if urls.is_empty() {
+                return None;
+            }
+
+            let secret = match std::env::var("WEBHOOK_SECRET") {
+                Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+                _ => {
+                    log!("WEBHOOK_URLS is set but WEBHOOK_SECRET is not, webhooks are disabled.");
+                    return None;
+                }
+            };
+
+            let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+                Ok(client) => client,
+                Err(e) => {
+                    log!("Could not create the HTTP client, webhooks are disabled: {}", e);
+                    return None;
+                }
+            };
+
+            Some(Config { urls, secret, client })
+        })
+        .as_ref()
+}
+
+/// The `X-Webhook-Signature` value for `body` sent at `timestamp`.
+pub fn signature(secret: &[u8], timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Queues `data` as `event` for every configured URL.
+pub async fn enqueue<T: Serialize>(
+    conn: &mut sqlx::SqliteConnection,
+    email_id: i64,
+    event: &str,
+    data: T,
+) -> Result<(), sqlx::Error> {
+    let Some(config) = config() else {
+        return Ok(());
+    };
+    let body = serde_json::to_string(&Envelope { event, data }).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+
+    for url in &config.urls {
+        let mut id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut id);
+        let delivery = Delivery {
+            id: hex::encode(id),
+            email_id,
+            event: event.to_string(),
+            url: url.clone(),
+            body: body.clone(),
+        };
+        crate::jobs::enqueue(&mut *conn, Some(email_id), &Job::Webhook(delivery)).await?;
+    }
+    Ok(())
+}
+
+/// Makes one attempt at a delivery, run by the job workers.
+pub async fn deliver(delivery: &Delivery, attempt: i64) -> Result<(), JobError> {
+    let config = config().ok_or_else(|| JobError::Permanent("webhooks are no longer configured".to_string()))?;
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let result = config
+        .client
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature(&config.secret, timestamp, delivery.body.as_bytes()))
+        .header(ID_HEADER, &delivery.id)
+        .header(EVENT_HEADER, &delivery.event)
+        .body(delivery.body.clone())
+        .send()
+        .await;
+
+    let (status, error) = match result {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+        Ok(response) => (Some(response.status().as_u16()), Some(format!("unexpected status {}", response.status()))),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    record_attempt(delivery, attempt, status, error.as_deref()).await;
+
+    match error {
+        None => {
+            log!("Webhook {} for inquiry {} delivered to {}.", delivery.event, delivery.email_id, delivery.url);
+            Ok(())
+        }
+        Some(e) => Err(JobError::Retry(format!("webhook to {}: {}", delivery.url, e))),
+    }
+}
+
+async fn record_attempt(delivery: &Delivery, attempt: i64, status: Option<u16>, error: Option<&str>) {
+    let result = match db().await {
+        Ok(mut conn) => sqlx::query(
+            "INSERT INTO webhook_attempts (delivery_id, email_id, event, url, attempt, status_code, error) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&delivery.id)
+        .bind(delivery.email_id)
+        .bind(&delivery.event)
+        .bind(&delivery.url)
+        .bind(attempt)
+        .bind(status)
+        .bind(error)
+        .execute(&mut conn)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+
+    if let Err(e) = result {
+        log!("Could not record webhook attempt {} of {}: {}", attempt, delivery.id, e);
+    }
+}